@@ -1,7 +1,27 @@
 // Basic allocator module - bump allocator and arena allocator with optimizations
 
-use std::alloc::{alloc, dealloc, Layout};
-use std::ptr::NonNull;
+use alloc::alloc::{alloc, dealloc};
+#[cfg(feature = "alloc-stats")]
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::alloc::Layout;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::ops::{Deref, Range};
+use core::ptr::{self, NonNull};
+use core::slice;
+#[cfg(feature = "std")]
+use std::alloc::GlobalAlloc;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard};
+
+/// Saved bump position of a `BumpAllocator`, used to roll back scoped allocations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    offset: usize,
+    padding: usize,
+}
 
 /// Bump allocator - simple linear allocator for fast allocation
 /// Allocations are not freed individually, only the entire arena can be reset
@@ -10,6 +30,11 @@ pub struct BumpAllocator {
     current: *mut u8,
     end: *mut u8,
     size: usize,
+    base_align: usize, // Alignment of the backing buffer
+    padding: usize,    // Bytes skipped to satisfy alignment
+    owned: bool,       // Whether the buffer was allocated by us and must be freed
+    #[cfg(feature = "debug-tracking")]
+    allocations: Vec<(usize, usize)>, // (offset from start, size) of each live allocation
 }
 
 unsafe impl Send for BumpAllocator {}
@@ -18,11 +43,17 @@ unsafe impl Sync for BumpAllocator {}
 impl BumpAllocator {
     /// Create a new bump allocator with the specified size
     pub fn new(size: usize) -> Result<Self, &'static str> {
+        Self::with_align(size, 8)
+    }
+
+    /// Create a new bump allocator whose backing buffer starts at a multiple
+    /// of `base_align`, so the first allocation needs no padding up to that alignment
+    pub fn with_align(size: usize, base_align: usize) -> Result<Self, &'static str> {
         if size == 0 {
             return Err("Allocator size must be greater than 0");
         }
 
-        let layout = Layout::from_size_align(size, 8).map_err(|_| "Invalid layout")?;
+        let layout = Layout::from_size_align(size, base_align).map_err(|_| "Invalid layout")?;
 
         unsafe {
             let ptr = alloc(layout);
@@ -35,38 +66,170 @@ impl BumpAllocator {
                 current: ptr,
                 end: ptr.add(size),
                 size,
+                base_align,
+                padding: 0,
+                owned: true,
+                #[cfg(feature = "debug-tracking")]
+                allocations: Vec::new(),
             })
         }
     }
 
+    /// Create a bump allocator over a caller-provided buffer, for targets
+    /// without a global heap. The buffer is never freed by the allocator
+    pub fn from_buffer(buf: &'static mut [u8]) -> Self {
+        let size = buf.len();
+        let start = buf.as_mut_ptr();
+        Self {
+            start,
+            current: start,
+            end: unsafe { start.add(size) },
+            size,
+            base_align: 1,
+            padding: 0,
+            owned: false,
+            #[cfg(feature = "debug-tracking")]
+            allocations: Vec::new(),
+        }
+    }
+
     /// Allocate memory of the specified size and alignment
     /// Optimized for common alignment values (8, 16, 32, 64)
     pub fn allocate(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let current_addr = self.current as usize;
+
         // Optimize alignment calculation for power-of-2 alignments
         let align_offset = if align.is_power_of_two() {
             // Fast path: use bitwise operations for power-of-2 alignment
             let mask = align - 1;
-            let current_addr = self.current as usize;
-            let aligned_addr = (current_addr + mask) & !mask;
+            let aligned_addr = current_addr.checked_add(mask)? & !mask;
             aligned_addr - current_addr
         } else {
             self.current.align_offset(align)
         };
 
-        let aligned_ptr = unsafe { self.current.add(align_offset) };
-        let new_current = unsafe { aligned_ptr.add(size) };
-
-        if new_current > self.end {
+        // Check bounds on addresses before forming pointers, so huge requests
+        // fail cleanly instead of wrapping around
+        let new_addr = current_addr.checked_add(align_offset)?.checked_add(size)?;
+        if new_addr > self.end as usize {
             return None; // Out of memory
         }
 
+        let aligned_ptr = unsafe { self.current.add(align_offset) };
+        let new_current = unsafe { aligned_ptr.add(size) };
+
         self.current = new_current;
+        self.padding += align_offset;
+        #[cfg(feature = "debug-tracking")]
+        self.allocations.push((
+            unsafe { aligned_ptr.offset_from(self.start) as usize },
+            size,
+        ));
         NonNull::new(aligned_ptr)
     }
 
     /// Reset the allocator, freeing all allocations
     pub fn reset(&mut self) {
         self.current = self.start;
+        self.padding = 0;
+        #[cfg(feature = "debug-tracking")]
+        self.allocations.clear();
+    }
+
+    /// Allocate memory for a `Layout`
+    pub fn allocate_layout(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate(layout.size(), layout.align())
+    }
+
+    /// Allocate memory like `allocate`, with every byte set to zero
+    pub fn allocate_zeroed(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let ptr = self.allocate(size, align)?;
+        unsafe { ptr.as_ptr().write_bytes(0, size) };
+        Some(ptr)
+    }
+
+    /// Free the most recent allocation if `ptr..ptr + size` ends exactly at the
+    /// bump position, returning false and leaving state untouched otherwise.
+    /// Alignment padding placed before the freed block stays consumed until the
+    /// next reset or restore
+    pub fn free_last(&mut self, ptr: NonNull<u8>, size: usize) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        if addr < self.start as usize || addr.checked_add(size) != Some(self.current as usize) {
+            return false;
+        }
+
+        self.current = ptr.as_ptr();
+        #[cfg(feature = "debug-tracking")]
+        self.allocations.pop();
+        true
+    }
+
+    /// Resize an allocation to `new_size` bytes
+    /// The top-most allocation is extended (or shrunk) in place when it fits;
+    /// otherwise a new block is allocated and the old contents copied over.
+    /// The new block keeps the old pointer's alignment, up to 64 bytes
+    pub fn grow(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        let addr = ptr.as_ptr() as usize;
+        if addr >= self.start as usize && addr.checked_add(old_size) == Some(self.current as usize)
+        {
+            let new_end = addr.checked_add(new_size)?;
+            if new_end <= self.end as usize {
+                self.current = unsafe { ptr.as_ptr().add(new_size) };
+                #[cfg(feature = "debug-tracking")]
+                if let Some(last) = self.allocations.last_mut() {
+                    last.1 = new_size;
+                }
+                return Some(ptr);
+            }
+        }
+
+        let align = (1usize << addr.trailing_zeros().min(6)).max(1);
+        let new_ptr = self.allocate(new_size, align)?;
+        unsafe {
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size.min(new_size));
+        }
+        Some(new_ptr)
+    }
+
+    /// Capture the current bump position
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            offset: self.used(),
+            padding: self.padding,
+        }
+    }
+
+    /// Rewind to a previously captured position, freeing everything allocated
+    /// since. Checkpoints ahead of the current position are rejected so a stale
+    /// checkpoint can never extend the live region
+    pub fn restore(&mut self, checkpoint: Checkpoint) -> Result<(), &'static str> {
+        if checkpoint.offset > self.used() {
+            return Err("Checkpoint is ahead of the current position");
+        }
+
+        self.current = unsafe { self.start.add(checkpoint.offset) };
+        self.padding = checkpoint.padding;
+        #[cfg(feature = "debug-tracking")]
+        self.allocations
+            .retain(|&(offset, _)| offset < checkpoint.offset);
+        Ok(())
+    }
+
+    /// Iterate every live allocation as (pointer, requested size), oldest first
+    #[cfg(feature = "debug-tracking")]
+    pub fn iter_allocations(&self) -> impl Iterator<Item = (NonNull<u8>, usize)> + '_ {
+        self.allocations.iter().map(move |&(offset, size)| {
+            let ptr = unsafe { self.start.add(offset) };
+            (
+                NonNull::new(ptr).expect("allocation inside a live buffer"),
+                size,
+            )
+        })
     }
 
     /// Get the number of bytes currently allocated
@@ -74,6 +237,12 @@ impl BumpAllocator {
         unsafe { self.current.offset_from(self.start) as usize }
     }
 
+    /// Get the number of used bytes lost to alignment padding rather than
+    /// handed out to callers
+    pub fn wasted_bytes(&self) -> usize {
+        self.padding
+    }
+
     /// Get the total capacity
     pub fn capacity(&self) -> usize {
         self.size
@@ -82,34 +251,130 @@ impl BumpAllocator {
 
 impl Drop for BumpAllocator {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
         unsafe {
-            let layout = Layout::from_size_align(self.size, 8).unwrap();
+            let layout = Layout::from_size_align(self.size, self.base_align).unwrap();
             dealloc(self.start, layout);
         }
     }
 }
 
+/// `GlobalAlloc` adapter over a `BumpAllocator` for programs that never free
+/// memory until shutdown
+///
+/// `alloc` honors the requested `Layout` alignment and returns null once the
+/// buffer is exhausted; `dealloc` is a no-op, as with any bump allocator.
+/// Using it as `#[global_allocator]` requires a statically-sized backing buffer
+/// that is not itself obtained from the global allocator, so it must not wrap a
+/// `BumpAllocator::new` buffer there. The `debug-tracking` feature also
+/// allocates on every call and must be off in that setting.
+#[cfg(feature = "std")]
+pub struct BumpGlobalAlloc {
+    inner: Mutex<BumpAllocator>,
+}
+
+#[cfg(feature = "std")]
+impl BumpGlobalAlloc {
+    pub fn new(allocator: BumpAllocator) -> Self {
+        Self {
+            inner: Mutex::new(allocator),
+        }
+    }
+
+    /// Get the number of bytes handed out so far
+    pub fn used(&self) -> usize {
+        self.inner.lock().map_or(0, |allocator| allocator.used())
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl GlobalAlloc for BumpGlobalAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Allocation must not panic, so a poisoned lock is reported as OOM
+        match self.inner.lock() {
+            Ok(mut allocator) => allocator
+                .allocate(layout.size(), layout.align())
+                .map_or(ptr::null_mut(), NonNull::as_ptr),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        // Bump semantics: memory is only reclaimed when the allocator is dropped
+    }
+}
+
 /// Memory pool for fixed-size allocations
+/// A pool created with `with_max_blocks` grows by another `capacity` blocks
+/// whenever it runs dry, up to `max_blocks` blocks in total
 pub struct MemoryPool {
     block_size: usize,
+    capacity: usize,                // Blocks added by each backing region
+    max_blocks: usize,              // Growth stops at this many blocks
+    regions: Vec<(*mut u8, usize)>, // Start and block count of each backing region
     blocks: Vec<*mut u8>,
     free_list: Vec<*mut u8>,
-    pool_size: usize,
+    in_use: Vec<u64>, // Bitset of allocated blocks, indexed by block number
 }
 
+unsafe impl Send for MemoryPool {}
+
+/// Pattern written over free pool blocks with the `debug-poison` feature
+#[cfg(feature = "debug-poison")]
+pub const FREED_POISON: u8 = 0xDD;
+
+/// Pattern written over freshly allocated pool blocks with the `debug-poison` feature
+#[cfg(feature = "debug-poison")]
+pub const ALLOCATED_POISON: u8 = 0xCD;
+
 impl MemoryPool {
     /// Create a new memory pool with specified block size and capacity
     pub fn new(block_size: usize, capacity: usize) -> Result<Self, &'static str> {
+        Self::with_max_blocks(block_size, capacity, capacity)
+    }
+
+    /// Create a memory pool that starts with `capacity` blocks and grows by
+    /// another `capacity` blocks when exhausted, up to `max_blocks` in total
+    pub fn with_max_blocks(
+        block_size: usize,
+        capacity: usize,
+        max_blocks: usize,
+    ) -> Result<Self, &'static str> {
         if block_size == 0 || capacity == 0 {
             return Err("Block size and capacity must be greater than 0");
         }
+        if max_blocks < capacity {
+            return Err("Max blocks must be at least the capacity");
+        }
 
         // Align block size to next power of 2 for better performance
-        let aligned_block_size = block_size.next_power_of_two();
-        let pool_size = aligned_block_size * capacity;
+        let aligned_block_size = block_size
+            .checked_next_power_of_two()
+            .ok_or("Block size too large")?;
 
+        let mut pool = Self {
+            block_size: aligned_block_size,
+            capacity,
+            max_blocks,
+            regions: Vec::new(),
+            blocks: Vec::new(),
+            free_list: Vec::new(),
+            in_use: Vec::new(),
+        };
+        pool.add_region(capacity)?;
+        Ok(pool)
+    }
+
+    /// Allocate a backing region of `count` blocks and add them to the free list
+    fn add_region(&mut self, count: usize) -> Result<(), &'static str> {
+        let region_size = self
+            .block_size
+            .checked_mul(count)
+            .ok_or("Pool size overflows usize")?;
         let layout =
-            Layout::from_size_align(pool_size, aligned_block_size).map_err(|_| "Invalid layout")?;
+            Layout::from_size_align(region_size, self.block_size).map_err(|_| "Invalid layout")?;
 
         unsafe {
             let ptr = alloc(layout);
@@ -117,43 +382,98 @@ impl MemoryPool {
                 return Err("Failed to allocate memory pool");
             }
 
-            let mut blocks = Vec::with_capacity(capacity);
-            let mut free_list = Vec::with_capacity(capacity);
+            #[cfg(feature = "debug-poison")]
+            ptr.write_bytes(FREED_POISON, region_size);
 
-            // Initialize free list
-            for i in 0..capacity {
-                let block_ptr = ptr.add(i * aligned_block_size);
-                blocks.push(block_ptr);
-                free_list.push(block_ptr);
+            self.regions.push((ptr, count));
+            self.blocks.reserve(count);
+            self.free_list.reserve(count);
+            for i in 0..count {
+                let block_ptr = ptr.add(i * self.block_size);
+                self.blocks.push(block_ptr);
+                self.free_list.push(block_ptr);
             }
-
-            Ok(Self {
-                block_size: aligned_block_size,
-                blocks,
-                free_list,
-                pool_size,
-            })
         }
+        self.in_use.resize(self.blocks.len().div_ceil(64), 0);
+        Ok(())
     }
 
-    /// Allocate a block from the pool
+    /// Whether `allocate` can hand out a block, from the free list or by growing
+    fn can_allocate(&self) -> bool {
+        !self.free_list.is_empty() || self.blocks.len() < self.max_blocks
+    }
+
+    /// Allocate a block from the pool, growing it first if it is exhausted
+    /// and below `max_blocks`
     pub fn allocate(&mut self) -> Option<NonNull<u8>> {
-        self.free_list.pop().and_then(NonNull::new)
+        if self.free_list.is_empty() && self.blocks.len() < self.max_blocks {
+            let count = self.capacity.min(self.max_blocks - self.blocks.len());
+            self.add_region(count).ok()?;
+        }
+        let ptr = NonNull::new(self.free_list.pop()?)?;
+        if let Some(index) = self.block_index(ptr) {
+            self.in_use[index / 64] |= 1 << (index % 64);
+        }
+        #[cfg(feature = "debug-poison")]
+        unsafe {
+            let block = slice::from_raw_parts_mut(ptr.as_ptr(), self.block_size);
+            assert!(
+                block.iter().all(|&b| b == FREED_POISON),
+                "pool block at {:p} was written after being freed",
+                ptr
+            );
+            block.fill(ALLOCATED_POISON);
+        }
+        Some(ptr)
     }
 
     /// Deallocate a block back to the pool
-    pub fn deallocate(&mut self, ptr: NonNull<u8>) {
-        // Verify pointer is in pool range
-        let ptr_addr = ptr.as_ptr() as usize;
-        let pool_start = self.blocks[0] as usize;
-        let pool_end = pool_start + self.pool_size;
+    /// Returns false, leaving the pool untouched, if the pointer is not a block
+    /// of this pool or the block is already free (double free)
+    pub fn deallocate(&mut self, ptr: NonNull<u8>) -> bool {
+        let index = match self.block_index(ptr) {
+            Some(index) => index,
+            None => return false,
+        };
 
-        if ptr_addr >= pool_start && ptr_addr < pool_end {
-            // Check alignment
-            if (ptr_addr - pool_start).is_multiple_of(self.block_size) {
-                self.free_list.push(ptr.as_ptr());
+        let mask = 1 << (index % 64);
+        if self.in_use[index / 64] & mask == 0 {
+            return false;
+        }
+        self.in_use[index / 64] &= !mask;
+        #[cfg(feature = "debug-poison")]
+        unsafe {
+            ptr.as_ptr().write_bytes(FREED_POISON, self.block_size);
+        }
+        self.free_list.push(ptr.as_ptr());
+        true
+    }
+
+    /// Get the block number of a pointer to the start of one of this pool's blocks
+    fn block_index(&self, ptr: NonNull<u8>) -> Option<usize> {
+        // Blocks are numbered region by region, in the order regions were added
+        let mut first_block = 0;
+        for &(start, count) in &self.regions {
+            let offset = (ptr.as_ptr() as usize).wrapping_sub(start as usize);
+            if offset < count * self.block_size {
+                // Check alignment
+                if !offset.is_multiple_of(self.block_size) {
+                    return None;
+                }
+                return Some(first_block + offset / self.block_size);
             }
+            first_block += count;
         }
+        None
+    }
+
+    /// Check whether a pointer lies inside this pool's memory
+    pub fn contains(&self, ptr: NonNull<u8>) -> bool {
+        let ptr_addr = ptr.as_ptr() as usize;
+        self.regions.iter().any(|&(start, count)| {
+            let start = start as usize;
+            ptr_addr >= start && ptr_addr < start + count * self.block_size
+        })
     }
 
     /// Get number of free blocks
@@ -172,15 +492,21 @@ impl MemoryPool {
         for &block in &self.blocks {
             self.free_list.push(block);
         }
+        self.in_use.fill(0);
+        #[cfg(feature = "debug-poison")]
+        for &(start, count) in &self.regions {
+            unsafe { start.write_bytes(FREED_POISON, count * self.block_size) };
+        }
     }
 }
 
 impl Drop for MemoryPool {
     fn drop(&mut self) {
-        if !self.blocks.is_empty() {
+        for &(start, count) in &self.regions {
             unsafe {
-                let layout = Layout::from_size_align(self.pool_size, self.block_size).unwrap();
-                dealloc(self.blocks[0], layout);
+                let layout =
+                    Layout::from_size_align(count * self.block_size, self.block_size).unwrap();
+                dealloc(start, layout);
             }
         }
     }
@@ -192,65 +518,283 @@ pub struct Arena {
     current_allocator: usize,
     allocator_size: usize,
     pools: Vec<MemoryPool>, // Memory pools for common sizes
+    min_alignment: usize,   // Every allocation is aligned to at least this
+    #[cfg(feature = "alloc-stats")]
+    stats: AllocStats,
+}
+
+/// Count and total requested bytes of the allocations in one size bucket
+#[cfg(feature = "alloc-stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeBucket {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Allocation size histogram recorded by an `Arena` with the `alloc-stats` feature
+/// Sizes are bucketed by rounding up to the next power of two
+#[cfg(feature = "alloc-stats")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub buckets: BTreeMap<usize, SizeBucket>,
+    pub pool_hits: u64,
+    pub bump_allocations: u64,
 }
 
+#[cfg(feature = "alloc-stats")]
+impl AllocStats {
+    fn record(&mut self, size: usize, pool_hit: bool) {
+        let bucket = self.buckets.entry(size.next_power_of_two()).or_default();
+        bucket.count += 1;
+        bucket.bytes += size as u64;
+        if pool_hit {
+            self.pool_hits += 1;
+        } else {
+            self.bump_allocations += 1;
+        }
+    }
+
+    /// Get the total number of allocations recorded
+    pub fn total_count(&self) -> u64 {
+        self.pool_hits + self.bump_allocations
+    }
+}
+
+/// Utilization and address range of one of an `Arena`'s backing allocators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocatorInfo {
+    pub used: usize,
+    pub capacity: usize,
+    pub range: Range<usize>, // Addresses of the allocator's buffer
+    pub current: bool,       // Whether new bump allocations go to this allocator
+}
+
+/// Where `Arena::allocate_tracked` took an allocation from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocSource {
+    /// Index of the pool, in the order its size class was configured
+    Pool(usize),
+    /// Index of the backing allocator, as listed by `allocators_info`
+    Bump(usize),
+}
+
+/// Copy of an arena's live memory and allocation state, taken by
+/// `Arena::snapshot` and written back by `Arena::restore`
+#[derive(Debug, Clone)]
+pub struct ArenaSnapshot {
+    allocators: Vec<BumpSnapshot>,
+    current_allocator: usize,
+    pools: Vec<PoolSnapshot>,
+}
+
+#[derive(Debug, Clone)]
+struct BumpSnapshot {
+    start: usize, // Identifies the allocator the bytes came from
+    used: Vec<u8>,
+    padding: usize,
+    #[cfg(feature = "debug-tracking")]
+    allocations: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone)]
+struct PoolSnapshot {
+    start: usize,      // First region's address, identifying the pool
+    regions: usize,    // Backing regions covered by `contents`
+    contents: Vec<u8>, // Bytes of each region, concatenated
+    free_list: Vec<usize>,
+    in_use: Vec<u64>,
+}
+
+/// Default pool size classes as `(block_size, capacity)` pairs
+const DEFAULT_POOLS: &[(usize, usize)] = &[(8, 256), (16, 256), (32, 256), (64, 256), (128, 256)];
+
 impl Arena {
     /// Create a new arena with the specified allocator size
     pub fn new(allocator_size: usize) -> Result<Self, &'static str> {
+        Self::with_pools(allocator_size, DEFAULT_POOLS)
+    }
+
+    /// Create a new arena with custom pool size classes, given as
+    /// `(block_size, capacity)` pairs, in place of the default pools
+    pub fn with_pools(
+        allocator_size: usize,
+        pool_specs: &[(usize, usize)],
+    ) -> Result<Self, &'static str> {
         let first_allocator = BumpAllocator::new(allocator_size)?;
 
-        // Create memory pools for common sizes (8, 16, 32, 64, 128 bytes)
-        let mut pools = Vec::new();
-        for &size in &[8, 16, 32, 64, 128] {
-            if let Ok(pool) = MemoryPool::new(size, 256) {
-                pools.push(pool);
-            }
-        }
+        let pools = pool_specs
+            .iter()
+            .map(|&(block_size, capacity)| MemoryPool::new(block_size, capacity))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             allocators: vec![first_allocator],
             current_allocator: 0,
             allocator_size,
             pools,
+            min_alignment: 1,
+            #[cfg(feature = "alloc-stats")]
+            stats: AllocStats::default(),
         })
     }
 
+    /// Set the minimum alignment applied to every allocation, regardless of
+    /// the alignment requested. Trades some padding for simpler callers
+    pub fn set_min_alignment(&mut self, align: usize) -> Result<(), &'static str> {
+        if !align.is_power_of_two() {
+            return Err("Minimum alignment must be a power of two");
+        }
+        self.min_alignment = align;
+        Ok(())
+    }
+
+    /// Get the minimum alignment applied to every allocation
+    pub fn min_alignment(&self) -> usize {
+        self.min_alignment
+    }
+
     /// Allocate memory, using pools for common sizes, creating a new allocator if needed
     pub fn allocate(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
-        // Try memory pools for common sizes
-        for pool in &mut self.pools {
-            if pool.block_size >= size && pool.block_size % align == 0 {
-                if let Some(ptr) = pool.allocate() {
-                    return Some(ptr);
-                }
-            }
+        self.allocate_tracked(size, align).map(|(ptr, _)| ptr)
+    }
+
+    /// Allocate memory like `allocate` and report which pool or backing
+    /// allocator served it
+    pub fn allocate_tracked(
+        &mut self,
+        size: usize,
+        align: usize,
+    ) -> Option<(NonNull<u8>, AllocSource)> {
+        let align = align.max(self.min_alignment);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(size, align, "arena allocate");
+
+        // Try the tightest fitting memory pool that has a free block
+        let best = self
+            .pools
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, pool)| {
+                pool.block_size >= size && pool.block_size % align == 0 && pool.can_allocate()
+            })
+            .min_by_key(|(_, pool)| pool.block_size);
+        if let Some((index, ptr)) = best.and_then(|(index, pool)| Some((index, pool.allocate()?))) {
+            #[cfg(feature = "alloc-stats")]
+            self.stats.record(size, true);
+            return Some((ptr, AllocSource::Pool(index)));
         }
 
         // Try current allocator
         if let Some(ptr) = self.allocators[self.current_allocator].allocate(size, align) {
-            return Some(ptr);
+            #[cfg(feature = "alloc-stats")]
+            self.stats.record(size, false);
+            return Some((ptr, AllocSource::Bump(self.current_allocator)));
         }
 
         // Create new allocator if current is full
-        match BumpAllocator::new(self.allocator_size.max(size * 2)) {
+        let new_size = self.allocator_size.max(size.checked_mul(2)?);
+        match BumpAllocator::new(new_size) {
             Ok(mut new_allocator) => {
                 if let Some(ptr) = new_allocator.allocate(size, align) {
                     self.allocators.push(new_allocator);
                     self.current_allocator = self.allocators.len() - 1;
-                    Some(ptr)
+                    #[cfg(feature = "alloc-stats")]
+                    self.stats.record(size, false);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        capacity = new_size,
+                        allocators = self.allocators.len(),
+                        "arena grew new backing allocator"
+                    );
+                    Some((ptr, AllocSource::Bump(self.current_allocator)))
                 } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(size, align, "arena allocation does not fit new allocator");
                     None
                 }
             }
-            Err(_) => None,
+            Err(_err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(size, align, error = _err, "arena out of memory");
+                None
+            }
+        }
+    }
+
+    /// Allocate memory for a `Layout`
+    pub fn allocate_layout(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate(layout.size(), layout.align())
+    }
+
+    /// Allocate memory like `allocate`, with every byte set to zero
+    /// Pool blocks are recycled and may hold stale data, so they are zeroed too
+    pub fn allocate_zeroed(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let ptr = self.allocate(size, align)?;
+        unsafe { ptr.as_ptr().write_bytes(0, size) };
+        Some(ptr)
+    }
+
+    /// Move `value` into the arena and return a reference to it
+    /// The value is never dropped; its memory is reclaimed by `reset`
+    pub fn alloc<T>(&mut self, value: T) -> Option<&mut T> {
+        let ptr = if size_of::<T>() == 0 {
+            NonNull::<T>::dangling()
+        } else {
+            self.allocate(size_of::<T>(), align_of::<T>())?.cast::<T>()
+        };
+        unsafe {
+            ptr.as_ptr().write(value);
+            Some(&mut *ptr.as_ptr())
         }
     }
 
+    /// Allocate uninitialized space for `len` values of `T`
+    pub fn alloc_slice<T>(&mut self, len: usize) -> Option<&mut [MaybeUninit<T>]> {
+        let layout = Layout::array::<T>(len).ok()?;
+        let ptr = if layout.size() == 0 {
+            NonNull::<MaybeUninit<T>>::dangling()
+        } else {
+            self.allocate(layout.size(), layout.align())?.cast()
+        };
+        unsafe { Some(slice::from_raw_parts_mut(ptr.as_ptr(), len)) }
+    }
+
+    /// Allocate memory that is handed back to the arena when the returned guard
+    /// is dropped, giving pool-backed allocations RAII semantics
+    pub fn alloc_scoped(&mut self, size: usize, align: usize) -> Option<ScopedAlloc<'_>> {
+        let ptr = self.allocate(size, align)?;
+        Some(ScopedAlloc {
+            arena: self,
+            ptr,
+            size,
+        })
+    }
+
+    /// Iterate over the backing bump allocators, in creation order
+    pub fn allocators_info(&self) -> impl Iterator<Item = AllocatorInfo> + '_ {
+        self.allocators
+            .iter()
+            .enumerate()
+            .map(move |(index, allocator)| AllocatorInfo {
+                used: allocator.used(),
+                capacity: allocator.capacity(),
+                range: allocator.start as usize..allocator.end as usize,
+                current: index == self.current_allocator,
+            })
+    }
+
+    /// Get the allocation size histogram, accumulated since the arena was created
+    #[cfg(feature = "alloc-stats")]
+    pub fn allocation_stats(&self) -> &AllocStats {
+        &self.stats
+    }
+
     /// Deallocate memory (returns to pool if applicable)
     pub fn deallocate(&mut self, ptr: NonNull<u8>, size: usize) {
-        // Try to return to appropriate pool
+        // allocate() rounds sizes up to a pool's block size and falls through to
+        // larger pools when one is exhausted, so find the pool owning the pointer
         for pool in &mut self.pools {
-            if pool.block_size == size {
+            if pool.block_size >= size && pool.contains(ptr) {
                 pool.deallocate(ptr);
                 return;
             }
@@ -269,6 +813,36 @@ impl Arena {
         self.current_allocator = 0;
     }
 
+    /// Free every unused backing allocator except the first
+    /// Call after `reset` to release memory retained from an earlier spike
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(1);
+    }
+
+    /// Free unused trailing backing allocators until at most `max_retained`
+    /// remain (never fewer than one), keeping a few warm for the next spike.
+    /// Allocators at or before the current one, or holding data, are kept
+    pub fn shrink_to(&mut self, max_retained: usize) {
+        let keep = max_retained.max(1).max(self.current_allocator + 1);
+        while self.allocators.len() > keep {
+            match self.allocators.last() {
+                Some(last) if last.used() == 0 => {
+                    self.allocators.pop();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Check whether a pointer lies inside memory owned by this arena
+    pub fn contains(&self, ptr: NonNull<u8>) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        self.allocators
+            .iter()
+            .any(|a| (a.start as usize..a.end as usize).contains(&addr))
+            || self.pools.iter().any(|pool| pool.contains(ptr))
+    }
+
     /// Get total memory used across all allocators and pools
     pub fn total_used(&self) -> usize {
         let allocator_used: usize = self.allocators.iter().map(|a| a.used()).sum();
@@ -284,6 +858,261 @@ impl Arena {
     pub fn total_capacity(&self) -> usize {
         self.allocators.iter().map(|a| a.capacity()).sum()
     }
+
+    /// Copy the used bytes of every backing allocator and pool along with the
+    /// bump positions and free lists, so the arena can be rolled back later
+    pub fn snapshot(&self) -> ArenaSnapshot {
+        let allocators = self
+            .allocators
+            .iter()
+            .map(|allocator| BumpSnapshot {
+                start: allocator.start as usize,
+                used: unsafe { slice::from_raw_parts(allocator.start, allocator.used()) }.to_vec(),
+                padding: allocator.padding,
+                #[cfg(feature = "debug-tracking")]
+                allocations: allocator.allocations.clone(),
+            })
+            .collect();
+        let pools = self
+            .pools
+            .iter()
+            .map(|pool| PoolSnapshot {
+                start: pool.regions[0].0 as usize,
+                regions: pool.regions.len(),
+                contents: pool
+                    .regions
+                    .iter()
+                    .flat_map(|&(start, count)| unsafe {
+                        slice::from_raw_parts(start, count * pool.block_size)
+                    })
+                    .copied()
+                    .collect(),
+                free_list: pool.free_list.iter().map(|&block| block as usize).collect(),
+                in_use: pool.in_use.clone(),
+            })
+            .collect();
+
+        ArenaSnapshot {
+            allocators,
+            current_allocator: self.current_allocator,
+            pools,
+        }
+    }
+
+    /// Roll the arena back to a snapshot of it, rewriting the saved bytes and
+    /// positions. Allocators created since the snapshot are reset
+    ///
+    /// Every pointer handed out after the snapshot was taken is invalid once this
+    /// returns. Fails without changing anything if the snapshot came from another
+    /// arena or backing allocators it covers have since been freed
+    pub fn restore(&mut self, snapshot: &ArenaSnapshot) -> Result<(), &'static str> {
+        let allocators_match = snapshot.allocators.len() <= self.allocators.len()
+            && snapshot
+                .allocators
+                .iter()
+                .zip(&self.allocators)
+                .all(|(saved, allocator)| saved.start == allocator.start as usize);
+        let pools_match = snapshot.pools.len() == self.pools.len()
+            && snapshot.pools.iter().zip(&self.pools).all(|(saved, pool)| {
+                saved.start == pool.regions[0].0 as usize && saved.regions <= pool.regions.len()
+            });
+        if !allocators_match || !pools_match {
+            return Err("Snapshot does not match this arena");
+        }
+
+        for (index, allocator) in self.allocators.iter_mut().enumerate() {
+            let Some(saved) = snapshot.allocators.get(index) else {
+                allocator.reset();
+                continue;
+            };
+            unsafe {
+                ptr::copy_nonoverlapping(saved.used.as_ptr(), allocator.start, saved.used.len());
+                allocator.current = allocator.start.add(saved.used.len());
+            }
+            allocator.padding = saved.padding;
+            #[cfg(feature = "debug-tracking")]
+            {
+                allocator.allocations = saved.allocations.clone();
+            }
+        }
+        for (pool, saved) in self.pools.iter_mut().zip(&snapshot.pools) {
+            let mut offset = 0;
+            for &(start, count) in &pool.regions[..saved.regions] {
+                let len = count * pool.block_size;
+                unsafe { ptr::copy_nonoverlapping(saved.contents[offset..].as_ptr(), start, len) };
+                offset += len;
+            }
+            // Blocks of regions added since the snapshot are all free again
+            let saved_blocks = offset / pool.block_size;
+            #[cfg(feature = "debug-poison")]
+            for &(start, count) in &pool.regions[saved.regions..] {
+                unsafe { start.write_bytes(FREED_POISON, count * pool.block_size) };
+            }
+            pool.free_list = saved
+                .free_list
+                .iter()
+                .map(|&block| block as *mut u8)
+                .chain(pool.blocks[saved_blocks..].iter().copied())
+                .collect();
+            pool.in_use.clone_from(&saved.in_use);
+            pool.in_use.resize(pool.blocks.len().div_ceil(64), 0);
+        }
+        self.current_allocator = snapshot.current_allocator;
+        Ok(())
+    }
+
+    /// Run an allocation/deallocation/reset cycle on a fresh arena and verify
+    /// its bookkeeping invariants, returning a description of the first violation
+    pub fn self_test() -> Result<(), String> {
+        let mut arena = Arena::new(1024).map_err(|e| e.to_string())?;
+        let pool_sizes: Vec<usize> = arena.pools.iter().map(|p| p.block_size).collect();
+        let initial_free: Vec<usize> = arena.pools.iter().map(|p| p.free_count()).collect();
+        let largest_pool = pool_sizes.iter().copied().max().unwrap_or(0);
+        let bump_size = (largest_pool + 1).next_power_of_two().max(512);
+
+        // Run twice so the second round exercises memory handed back by reset
+        for round in 0..2 {
+            let mut live = Vec::new();
+
+            for &size in &pool_sizes {
+                let ptr = arena.allocate(size, 8).ok_or_else(|| {
+                    format!("round {}: pool allocation of {} bytes failed", round, size)
+                })?;
+                live.push((ptr, size));
+            }
+
+            let allocators_before = arena.allocators.len();
+            for _ in 0..4 {
+                let ptr = arena.allocate(bump_size, 8).ok_or_else(|| {
+                    format!(
+                        "round {}: bump allocation of {} bytes failed",
+                        round, bump_size
+                    )
+                })?;
+                live.push((ptr, bump_size));
+            }
+            if arena.allocators.len() <= allocators_before {
+                return Err(format!("round {}: bump region did not grow", round));
+            }
+
+            // No two live allocations may overlap
+            let mut ranges: Vec<(usize, usize)> = live
+                .iter()
+                .map(|(ptr, size)| (ptr.as_ptr() as usize, *size))
+                .collect();
+            ranges.sort_unstable();
+            for pair in ranges.windows(2) {
+                if pair[0].0 + pair[0].1 > pair[1].0 {
+                    return Err(format!(
+                        "round {}: live allocations at {:#x} and {:#x} overlap",
+                        round, pair[0].0, pair[1].0
+                    ));
+                }
+            }
+
+            for &(ptr, size) in &live {
+                if size <= largest_pool {
+                    arena.deallocate(ptr, size);
+                }
+            }
+            let free: Vec<usize> = arena.pools.iter().map(|p| p.free_count()).collect();
+            if free != initial_free {
+                return Err(format!(
+                    "round {}: pool free counts {:?} not restored to {:?}",
+                    round, free, initial_free
+                ));
+            }
+
+            arena.reset();
+            if arena.total_used() != 0 {
+                return Err(format!(
+                    "round {}: {} bytes still used after reset",
+                    round,
+                    arena.total_used()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Thread-safe arena shared between threads by reference
+///
+/// Every operation takes a single mutex around the wrapped `Arena` for just the
+/// duration of that call, so the critical section is the pool pop or bump
+/// itself. Threads allocating heavily will contend on the lock; give each
+/// thread its own `Arena` when that matters more than sharing.
+/// `reset` invalidates pointers held by every thread, not just the caller.
+#[cfg(feature = "std")]
+pub struct SyncArena {
+    inner: Mutex<Arena>,
+}
+
+#[cfg(feature = "std")]
+impl SyncArena {
+    /// Create a new shared arena with the specified allocator size
+    pub fn new(allocator_size: usize) -> Result<Self, &'static str> {
+        Ok(Self::from_arena(Arena::new(allocator_size)?))
+    }
+
+    /// Wrap an existing arena
+    pub fn from_arena(arena: Arena) -> Self {
+        Self {
+            inner: Mutex::new(arena),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Arena> {
+        self.inner.lock().expect("SyncArena lock poisoned")
+    }
+
+    /// Allocate memory, see `Arena::allocate`
+    pub fn allocate(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        self.lock().allocate(size, align)
+    }
+
+    /// Deallocate memory, see `Arena::deallocate`
+    pub fn deallocate(&self, ptr: NonNull<u8>, size: usize) {
+        self.lock().deallocate(ptr, size);
+    }
+
+    /// Reset all allocators and pools, see `Arena::reset`
+    pub fn reset(&self) {
+        self.lock().reset();
+    }
+
+    /// Get total memory used across all allocators and pools
+    pub fn total_used(&self) -> usize {
+        self.lock().total_used()
+    }
+
+    /// Unwrap into the inner arena
+    pub fn into_inner(self) -> Arena {
+        self.inner.into_inner().expect("SyncArena lock poisoned")
+    }
+}
+
+/// Guard for an arena allocation that is deallocated when dropped
+/// Dereferences to the allocated pointer
+pub struct ScopedAlloc<'a> {
+    arena: &'a mut Arena,
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+impl Deref for ScopedAlloc<'_> {
+    type Target = NonNull<u8>;
+
+    fn deref(&self) -> &NonNull<u8> {
+        &self.ptr
+    }
+}
+
+impl Drop for ScopedAlloc<'_> {
+    fn drop(&mut self) {
+        self.arena.deallocate(self.ptr, self.size);
+    }
 }
 
 #[cfg(test)]
@@ -304,6 +1133,337 @@ mod tests {
         assert_eq!(allocator.used(), 0);
     }
 
+    #[test]
+    fn test_bump_wasted_bytes() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+
+        let small = allocator.allocate(1, 1).unwrap();
+        assert_eq!(allocator.wasted_bytes(), 0);
+
+        let aligned = allocator.allocate(64, 64).unwrap();
+        assert_eq!(aligned.as_ptr() as usize % 64, 0);
+        let expected = aligned.as_ptr() as usize - (small.as_ptr() as usize + 1);
+        assert!(expected > 0);
+        assert_eq!(allocator.wasted_bytes(), expected);
+        assert_eq!(allocator.used(), 1 + expected + 64);
+
+        allocator.reset();
+        assert_eq!(allocator.wasted_bytes(), 0);
+    }
+
+    #[test]
+    fn test_bump_free_last() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+
+        let a = allocator.allocate(16, 8).unwrap();
+        let b = allocator.allocate(24, 8).unwrap();
+        assert!(allocator.free_last(b, 24));
+        let c = allocator.allocate(8, 8).unwrap();
+        let d = allocator.allocate(32, 8).unwrap();
+        assert_eq!(allocator.used(), 56);
+
+        // Only the top-most allocation can be freed
+        assert!(!allocator.free_last(a, 16));
+        assert!(!allocator.free_last(d, 16));
+        assert_eq!(allocator.used(), 56);
+
+        assert!(allocator.free_last(d, 32));
+        assert!(allocator.free_last(c, 8));
+        assert!(allocator.free_last(a, 16));
+        assert_eq!(allocator.used(), 0);
+        assert!(!allocator.free_last(a, 16));
+    }
+
+    #[test]
+    fn test_bump_allocate_zeroed() {
+        let mut allocator = BumpAllocator::new(256).unwrap();
+        let ptr = allocator.allocate(64, 8).unwrap();
+        unsafe { ptr.as_ptr().write_bytes(0xFF, 64) };
+        allocator.reset();
+
+        let ptr = allocator.allocate_zeroed(64, 8).unwrap();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), 64) };
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_bump_allocate_layout() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+        let layout = Layout::new::<[u64; 4]>();
+        let ptr = allocator.allocate_layout(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % layout.align(), 0);
+        assert_eq!(allocator.used(), 32);
+
+        let mut arena = Arena::new(1024).unwrap();
+        let ptr = arena.allocate_layout(layout).unwrap();
+        assert!(arena.pools.iter().any(|pool| pool.contains(ptr)));
+    }
+
+    #[test]
+    fn test_bump_from_buffer() {
+        let buf: &'static mut [u8; 256] = Box::leak(Box::new([0u8; 256]));
+        let raw = buf as *mut [u8; 256];
+
+        let mut allocator = BumpAllocator::from_buffer(buf);
+        assert_eq!(allocator.capacity(), 256);
+        let ptr = allocator.allocate(16, 8).unwrap();
+        assert!(ptr.as_ptr() as usize >= raw as usize);
+        assert!(allocator.allocate(512, 1).is_none());
+        drop(allocator);
+
+        // The allocator must not have freed the buffer, so reclaiming it is sound
+        drop(unsafe { Box::from_raw(raw) });
+    }
+
+    #[test]
+    fn test_bump_with_align() {
+        let mut allocator = BumpAllocator::with_align(1024, 64).unwrap();
+        let ptr = allocator.allocate(64, 64).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % 64, 0);
+        assert_eq!(allocator.wasted_bytes(), 0);
+
+        assert!(BumpAllocator::with_align(1024, 3).is_err());
+    }
+
+    #[test]
+    fn test_bump_grow_in_place() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+        let ptr = allocator.allocate(16, 8).unwrap();
+        unsafe { ptr.as_ptr().write_bytes(7, 16) };
+
+        let grown = allocator.grow(ptr, 16, 64).unwrap();
+        assert_eq!(grown, ptr);
+        assert_eq!(allocator.used(), 64);
+        let bytes = unsafe { std::slice::from_raw_parts(grown.as_ptr(), 16) };
+        assert!(bytes.iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn test_bump_grow_relocates() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+        let ptr = allocator.allocate(16, 8).unwrap();
+        for i in 0..16 {
+            unsafe { ptr.as_ptr().add(i).write(i as u8) };
+        }
+        // Another allocation on top forces the grow to relocate
+        allocator.allocate(8, 8).unwrap();
+
+        let grown = allocator.grow(ptr, 16, 32).unwrap();
+        assert_ne!(grown, ptr);
+        assert_eq!(grown.as_ptr() as usize % 8, 0);
+        let bytes = unsafe { std::slice::from_raw_parts(grown.as_ptr(), 16) };
+        assert_eq!(bytes, (0..16).collect::<Vec<u8>>().as_slice());
+        assert!(allocator.used() >= 16 + 8 + 32);
+
+        // Growing past the end of the buffer fails
+        assert!(allocator.grow(grown, 32, 4096).is_none());
+    }
+
+    #[test]
+    fn test_bump_checkpoint_restore() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+        allocator.allocate(16, 8).unwrap();
+
+        let outer = allocator.checkpoint();
+        allocator.allocate(32, 8).unwrap();
+
+        let inner = allocator.checkpoint();
+        allocator.allocate(64, 8).unwrap();
+        assert_eq!(allocator.used(), 112);
+
+        allocator.restore(inner).unwrap();
+        assert_eq!(allocator.used(), 48);
+
+        // Restoring the same checkpoint twice is a no-op the second time
+        allocator.restore(inner).unwrap();
+        assert_eq!(allocator.used(), 48);
+
+        allocator.restore(outer).unwrap();
+        assert_eq!(allocator.used(), 16);
+
+        // Padding introduced after a checkpoint is discarded by restore
+        let cp = allocator.checkpoint();
+        allocator.allocate(1, 1).unwrap();
+        allocator.allocate(8, 64).unwrap();
+        assert!(allocator.wasted_bytes() > 0);
+        allocator.restore(cp).unwrap();
+        assert_eq!(allocator.wasted_bytes(), 0);
+
+        // The inner checkpoint is now stale and must not extend the allocator
+        assert!(allocator.restore(inner).is_err());
+        assert_eq!(allocator.used(), 16);
+
+        // Memory is reused after a restore
+        let ptr = allocator.allocate(32, 8).unwrap();
+        allocator.restore(outer).unwrap();
+        assert_eq!(allocator.allocate(32, 8).unwrap(), ptr);
+    }
+
+    #[cfg(feature = "debug-tracking")]
+    #[test]
+    fn test_bump_iter_allocations() {
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+
+        let ptr1 = allocator.allocate(3, 1).unwrap();
+        let ptr2 = allocator.allocate(16, 8).unwrap();
+        let ptr3 = allocator.allocate(40, 16).unwrap();
+
+        let recorded: Vec<_> = allocator.iter_allocations().collect();
+        assert_eq!(recorded, vec![(ptr1, 3), (ptr2, 16), (ptr3, 40)]);
+
+        allocator.reset();
+        assert_eq!(allocator.iter_allocations().count(), 0);
+    }
+
+    #[test]
+    fn test_bump_global_alloc() {
+        let global = BumpGlobalAlloc::new(BumpAllocator::new(4096).unwrap());
+        assert_eq!(global.used(), 0);
+
+        unsafe {
+            let layout = Layout::new::<u64>();
+            let boxed = global.alloc(layout) as *mut u64;
+            assert!(!boxed.is_null());
+            boxed.write(42);
+            assert_eq!(*boxed, 42);
+            let after_first = global.used();
+            assert!(after_first >= 8);
+
+            // Over-aligned layouts are honored
+            let layout = Layout::from_size_align(48, 64).unwrap();
+            let aligned = global.alloc(layout);
+            assert_eq!(aligned as usize % 64, 0);
+            assert!(global.used() > after_first);
+
+            // dealloc is a no-op and exhaustion reports null
+            global.dealloc(aligned, layout);
+            assert!(global.used() >= after_first + 48);
+            assert!(global
+                .alloc(Layout::from_size_align(8192, 8).unwrap())
+                .is_null());
+        }
+    }
+
+    #[test]
+    fn test_pool_double_free() {
+        let mut pool = MemoryPool::new(16, 4).unwrap();
+        let a = pool.allocate().unwrap();
+        let b = pool.allocate().unwrap();
+        assert_eq!(pool.free_count(), 2);
+
+        assert!(pool.deallocate(a));
+        assert_eq!(pool.free_count(), 3);
+        assert!(!pool.deallocate(a));
+        assert_eq!(pool.free_count(), 3);
+
+        // Never-allocated blocks and interior pointers are rejected too
+        let interior = NonNull::new(unsafe { b.as_ptr().add(1) }).unwrap();
+        assert!(!pool.deallocate(interior));
+        assert!(pool.deallocate(b));
+        assert!(!pool.deallocate(b));
+        assert_eq!(pool.free_count(), 4);
+
+        // The same block is never handed out twice
+        let x = pool.allocate().unwrap();
+        let y = pool.allocate().unwrap();
+        assert_ne!(x, y);
+    }
+
+    #[test]
+    fn test_pool_growth() {
+        assert!(MemoryPool::with_max_blocks(16, 4, 3).is_err());
+
+        let mut pool = MemoryPool::with_max_blocks(16, 4, 10).unwrap();
+        let first: Vec<_> = (0..4).map(|_| pool.allocate().unwrap()).collect();
+        assert_eq!(pool.free_count(), 0);
+
+        // Exhausted, so the pool grows by another `capacity` blocks
+        let grown = pool.allocate().unwrap();
+        assert_eq!(pool.free_count(), 3);
+        assert_eq!(pool.allocated_count(), 5);
+        assert_eq!(pool.regions.len(), 2);
+        assert!(pool.contains(grown));
+        assert!(!first.contains(&grown));
+
+        // Blocks of either region free once, then count as double frees
+        assert!(pool.deallocate(grown));
+        assert!(!pool.deallocate(grown));
+        assert!(pool.deallocate(first[0]));
+        assert_eq!(pool.free_count(), 5);
+
+        // The last region is cut short to respect `max_blocks`
+        while pool.allocate().is_some() {}
+        assert_eq!(pool.allocated_count(), 10);
+        assert_eq!(pool.regions.len(), 3);
+        assert_eq!(pool.free_count(), 0);
+
+        pool.reset();
+        assert_eq!(pool.free_count(), 10);
+    }
+
+    #[test]
+    fn test_pool_without_growth() {
+        let mut pool = MemoryPool::new(16, 2).unwrap();
+        pool.allocate().unwrap();
+        pool.allocate().unwrap();
+        assert_eq!(pool.allocate(), None);
+        assert_eq!(pool.regions.len(), 1);
+    }
+
+    #[cfg(feature = "debug-poison")]
+    #[test]
+    fn test_pool_poisons_freed_blocks() {
+        let mut pool = MemoryPool::new(32, 4).unwrap();
+        let ptr = pool.allocate().unwrap();
+        let block = || unsafe { std::slice::from_raw_parts(ptr.as_ptr(), 32) };
+        assert!(block().iter().all(|&b| b == ALLOCATED_POISON));
+
+        unsafe { ptr.as_ptr().write_bytes(1, 32) };
+        assert!(pool.deallocate(ptr));
+        assert!(block().iter().all(|&b| b == FREED_POISON));
+
+        // The free list is LIFO, so the same block is handed out again
+        assert_eq!(pool.allocate(), Some(ptr));
+        assert!(block().iter().all(|&b| b == ALLOCATED_POISON));
+    }
+
+    #[cfg(feature = "debug-poison")]
+    #[test]
+    fn test_arena_restore_poisons_grown_pool_regions() {
+        let mut arena = Arena::with_pools(4096, &[]).unwrap();
+        arena
+            .pools
+            .push(MemoryPool::with_max_blocks(16, 2, 8).unwrap());
+        arena.allocate(16, 8).unwrap();
+        arena.allocate(16, 8).unwrap();
+        let snapshot = arena.snapshot();
+
+        // Grow the pool and dirty a block of the new region
+        let grown = arena.allocate(16, 8).unwrap();
+        assert_eq!(arena.pools[0].regions.len(), 2);
+        unsafe { grown.as_ptr().write_bytes(1, 16) };
+
+        arena.restore(&snapshot).unwrap();
+        let (start, count) = arena.pools[0].regions[1];
+        let region = unsafe { core::slice::from_raw_parts(start, count * 16) };
+        assert!(region.iter().all(|&b| b == FREED_POISON));
+        assert_eq!(arena.pools[0].free_count(), 2);
+        for _ in 0..2 {
+            arena.allocate(16, 8).unwrap();
+        }
+    }
+
+    #[cfg(feature = "debug-poison")]
+    #[test]
+    #[should_panic(expected = "written after being freed")]
+    fn test_pool_detects_write_after_free() {
+        let mut pool = MemoryPool::new(32, 4).unwrap();
+        let ptr = pool.allocate().unwrap();
+        pool.deallocate(ptr);
+        unsafe { ptr.as_ptr().write(0) };
+        pool.allocate();
+    }
+
     #[test]
     fn test_arena() {
         let mut arena = Arena::new(256).unwrap();
@@ -316,4 +1476,411 @@ mod tests {
         arena.reset();
         assert_eq!(arena.total_used(), 0);
     }
+
+    #[test]
+    fn test_arena_alloc_scoped() {
+        let mut arena = Arena::new(1024).unwrap();
+        let pool_index = arena.pools.iter().position(|p| p.block_size == 32).unwrap();
+        let initial_free = arena.pools[pool_index].free_count();
+
+        {
+            let block = arena.alloc_scoped(32, 8).unwrap();
+            unsafe { block.as_ptr().write_bytes(0xAB, 32) };
+            assert_eq!(block.arena.pools[pool_index].free_count(), initial_free - 1);
+        }
+
+        assert_eq!(arena.pools[pool_index].free_count(), initial_free);
+        assert_eq!(arena.total_used(), 0);
+    }
+
+    #[test]
+    fn test_arena_deallocate_rounded_size() {
+        let mut arena = Arena::new(1024).unwrap();
+        let pool_index = arena.pools.iter().position(|p| p.block_size == 32).unwrap();
+        let initial_free = arena.pools[pool_index].free_count();
+
+        // 20 bytes is served by the 32-byte pool
+        let ptr = arena.allocate(20, 4).unwrap();
+        assert_eq!(arena.pools[pool_index].free_count(), initial_free - 1);
+        arena.deallocate(ptr, 20);
+        assert_eq!(arena.pools[pool_index].free_count(), initial_free);
+
+        // Oversized and zero-sized deallocations are harmless no-ops
+        let big = arena.allocate(500, 8).unwrap();
+        arena.deallocate(big, 500);
+        arena.deallocate(big, 0);
+        assert_eq!(arena.pools[pool_index].free_count(), initial_free);
+    }
+
+    #[test]
+    fn test_arena_allocate_zeroed_pool_block() {
+        let mut arena = Arena::new(1024).unwrap();
+        let ptr = arena.allocate(32, 8).unwrap();
+        unsafe { ptr.as_ptr().write_bytes(0xAA, 32) };
+        arena.deallocate(ptr, 32);
+
+        // The pool hands the same dirty block straight back
+        let zeroed = arena.allocate_zeroed(32, 8).unwrap();
+        assert_eq!(zeroed, ptr);
+        let bytes = unsafe { std::slice::from_raw_parts(zeroed.as_ptr(), 32) };
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_arena_typed_alloc() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Point {
+            x: f64,
+            y: f64,
+            tag: u8,
+        }
+
+        let mut arena = Arena::new(1024).unwrap();
+        let point = arena
+            .alloc(Point {
+                x: 1.5,
+                y: -2.0,
+                tag: 7,
+            })
+            .unwrap();
+        assert_eq!((point as *mut Point as usize) % align_of::<Point>(), 0);
+        point.x += 1.0;
+        assert_eq!(
+            *point,
+            Point {
+                x: 2.5,
+                y: -2.0,
+                tag: 7
+            }
+        );
+
+        let slice = arena.alloc_slice::<u64>(10).unwrap();
+        assert_eq!(slice.len(), 10);
+        for (i, slot) in slice.iter_mut().enumerate() {
+            slot.write(i as u64 * 3);
+        }
+        let values: Vec<u64> = slice.iter().map(|v| unsafe { v.assume_init() }).collect();
+        assert_eq!(values, (0..10).map(|i| i * 3).collect::<Vec<_>>());
+
+        // Zero-sized types never touch the arena
+        let used = arena.total_used();
+        assert_eq!(*arena.alloc(()).unwrap(), ());
+        assert_eq!(arena.alloc_slice::<()>(5).unwrap().len(), 5);
+        assert_eq!(arena.alloc_slice::<u32>(0).unwrap().len(), 0);
+        assert_eq!(arena.total_used(), used);
+    }
+
+    #[test]
+    fn test_arena_best_fit_pool() {
+        let mut arena = Arena::with_pools(4096, &[(128, 4), (16, 1), (64, 4), (32, 4)]).unwrap();
+
+        let ptr = arena.allocate(10, 8).unwrap();
+        assert!(arena.pools[1].contains(ptr));
+
+        // With the 16-byte pool exhausted the next tightest pool is used
+        let ptr = arena.allocate(10, 8).unwrap();
+        assert!(arena.pools[3].contains(ptr));
+
+        // Pools whose blocks cannot satisfy the alignment are skipped
+        let ptr = arena.allocate(20, 64).unwrap();
+        assert!(arena.pools[2].contains(ptr));
+    }
+
+    #[test]
+    fn test_arena_with_pools() {
+        let mut arena = Arena::with_pools(4096, &[(512, 4), (256, 8)]).unwrap();
+        assert_eq!(arena.pools.len(), 2);
+
+        let ptr = arena.allocate(200, 8).unwrap();
+        assert!(arena.pools[1].contains(ptr));
+        assert_eq!(arena.pools[1].block_size, 256);
+        assert_eq!(arena.pools[1].allocated_count(), 1);
+        assert_eq!(arena.pools[0].allocated_count(), 0);
+        assert_eq!(arena.total_used(), 256);
+
+        // Sizes above every pool fall through to the bump region
+        arena.allocate(1024, 8).unwrap();
+        assert_eq!(arena.allocators[0].used(), 1024);
+
+        assert!(Arena::with_pools(4096, &[(0, 4)]).is_err());
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn test_arena_allocation_stats() {
+        let mut arena = Arena::new(4096).unwrap();
+        arena.allocate(8, 8).unwrap();
+        arena.allocate(6, 2).unwrap();
+        arena.allocate(100, 8).unwrap();
+        arena.allocate(300, 8).unwrap();
+        arena.allocate(500, 8).unwrap();
+
+        let stats = arena.allocation_stats();
+        assert_eq!(stats.pool_hits, 3);
+        assert_eq!(stats.bump_allocations, 2);
+        assert_eq!(stats.total_count(), 5);
+        assert_eq!(
+            stats.buckets[&8],
+            SizeBucket {
+                count: 2,
+                bytes: 14
+            }
+        );
+        assert_eq!(
+            stats.buckets[&128],
+            SizeBucket {
+                count: 1,
+                bytes: 100
+            }
+        );
+        assert_eq!(
+            stats.buckets[&512],
+            SizeBucket {
+                count: 2,
+                bytes: 800
+            }
+        );
+        assert_eq!(stats.buckets.len(), 3);
+    }
+
+    #[test]
+    fn test_arena_allocators_info() {
+        let mut arena = Arena::with_pools(1024, &[]).unwrap();
+        arena.allocate(1000, 8).unwrap();
+        arena.allocate(100, 8).unwrap();
+
+        let info: Vec<_> = arena.allocators_info().collect();
+        assert_eq!(info.len(), 2);
+        assert_eq!((info[0].used, info[0].capacity), (1000, 1024));
+        assert_eq!((info[1].used, info[1].capacity), (100, 1024));
+        assert!(!info[0].current && info[1].current);
+        assert_eq!(info[0].range.len(), 1024);
+        assert!(info[1]
+            .range
+            .contains(&(arena.allocate(8, 8).unwrap().as_ptr() as usize)));
+    }
+
+    #[test]
+    fn test_arena_min_alignment() {
+        let mut arena = Arena::new(1024).unwrap();
+        assert_eq!(arena.min_alignment(), 1);
+        assert!(arena.set_min_alignment(12).is_err());
+        arena.set_min_alignment(16).unwrap();
+
+        // Pool-sized and bump-sized requests are both rounded up
+        for size in [1, 3, 200, 301] {
+            let ptr = arena.allocate(size, 1).unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 16, 0, "size {}", size);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_arena_tracing_growth_event() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        struct Capture(Arc<Mutex<Vec<String>>>);
+
+        struct Message(String);
+
+        impl Visit for Message {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut message = Message(String::new());
+                event.record(&mut message);
+                self.0.lock().unwrap().push(message.0);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Capture(events.clone()), || {
+            let mut arena = Arena::new(256).unwrap();
+            arena.allocate(200, 8).unwrap();
+            assert!(events
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|e| e != "arena grew new backing allocator"));
+            arena.allocate(200, 8).unwrap();
+        });
+
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .any(|e| e == "arena grew new backing allocator"));
+        assert!(events.iter().any(|e| e == "arena allocate"));
+    }
+
+    #[test]
+    fn test_arena_shrink_to_fit() {
+        let mut arena = Arena::new(256).unwrap();
+        for _ in 0..6 {
+            arena.allocate(200, 8).unwrap();
+        }
+        assert!(arena.allocators.len() > 2);
+        let grown = arena.total_capacity();
+
+        // Allocators still holding data are never freed
+        arena.shrink_to_fit();
+        assert_eq!(arena.total_capacity(), grown);
+
+        arena.reset();
+        arena.shrink_to(2);
+        assert_eq!(arena.allocators.len(), 2);
+
+        arena.shrink_to_fit();
+        assert_eq!(arena.allocators.len(), 1);
+        assert_eq!(arena.total_capacity(), 256);
+        assert!(arena.allocate(200, 8).is_some());
+    }
+
+    #[test]
+    fn test_huge_sizes_fail_cleanly() {
+        let huge = usize::MAX / 2 + 1;
+
+        let mut allocator = BumpAllocator::new(1024).unwrap();
+        assert!(allocator.allocate(huge, 8).is_none());
+        assert!(allocator.allocate(usize::MAX, 1).is_none());
+        assert_eq!(allocator.used(), 0);
+
+        let mut arena = Arena::new(1024).unwrap();
+        assert!(arena.allocate(huge, 8).is_none());
+        assert!(arena.allocate(usize::MAX, 8).is_none());
+        assert_eq!(arena.total_used(), 0);
+
+        assert!(MemoryPool::new(huge + 1, 4).is_err());
+        assert!(MemoryPool::new(1024, huge).is_err());
+        assert!(BumpAllocator::new(huge).is_err());
+    }
+
+    #[test]
+    fn test_sync_arena_concurrent_allocations() {
+        let arena = SyncArena::new(4096).unwrap();
+        let sizes = [8, 24, 64, 100, 200, 1000];
+
+        let mut ranges: Vec<(usize, usize)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut ranges = Vec::new();
+                        for i in 0..300 {
+                            let size = sizes[i % sizes.len()];
+                            let ptr = arena.allocate(size, 8).unwrap();
+                            // Scribble over the whole block to catch shared memory
+                            unsafe { ptr.as_ptr().write_bytes(i as u8, size) };
+                            ranges.push((ptr.as_ptr() as usize, size));
+                        }
+                        ranges
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap())
+                .collect()
+        });
+
+        assert_eq!(ranges.len(), 8 * 300);
+        ranges.sort_unstable();
+        for pair in ranges.windows(2) {
+            assert!(
+                pair[0].0 + pair[0].1 <= pair[1].0,
+                "overlapping allocations"
+            );
+        }
+
+        arena.reset();
+        assert_eq!(arena.total_used(), 0);
+    }
+
+    #[test]
+    fn test_arena_allocate_tracked() {
+        let mut arena = Arena::new(1024).unwrap();
+        let (pooled, source) = arena.allocate_tracked(16, 8).unwrap();
+        assert_eq!(source, AllocSource::Pool(1));
+        assert_eq!(
+            arena.allocate_tracked(100, 8).unwrap().1,
+            AllocSource::Pool(4)
+        );
+        assert_eq!(
+            arena.allocate_tracked(512, 8).unwrap().1,
+            AllocSource::Bump(0)
+        );
+
+        // Too big for the first allocator, so a second one is created
+        let (big, source) = arena.allocate_tracked(1024, 8).unwrap();
+        assert_eq!(source, AllocSource::Bump(1));
+        let info: Vec<_> = arena.allocators_info().collect();
+        assert!(info[1].range.contains(&(big.as_ptr() as usize)));
+
+        arena.deallocate(pooled, 16);
+        assert_eq!(
+            arena.allocate_tracked(16, 8),
+            Some((pooled, AllocSource::Pool(1)))
+        );
+    }
+
+    #[test]
+    fn test_arena_snapshot_restore() {
+        let mut arena = Arena::new(256).unwrap();
+        let pooled = arena.allocate(16, 8).unwrap();
+        let bumped = arena.allocate(200, 8).unwrap();
+        unsafe {
+            pooled.as_ptr().write_bytes(1, 16);
+            bumped.as_ptr().write_bytes(2, 200);
+        }
+        let snapshot = arena.snapshot();
+        let used = arena.total_used();
+
+        // Overwrite, free and grow past the snapshot
+        unsafe {
+            pooled.as_ptr().write_bytes(9, 16);
+            bumped.as_ptr().write_bytes(9, 200);
+        }
+        arena.deallocate(pooled, 16);
+        arena.allocate(1000, 8).unwrap();
+        assert_eq!(arena.allocators.len(), 2);
+
+        arena.restore(&snapshot).unwrap();
+        assert_eq!(arena.total_used(), used);
+        assert_eq!(arena.current_allocator, 0);
+        unsafe {
+            assert!(slice::from_raw_parts(pooled.as_ptr(), 16)
+                .iter()
+                .all(|&b| b == 1));
+            assert!(slice::from_raw_parts(bumped.as_ptr(), 200)
+                .iter()
+                .all(|&b| b == 2));
+        }
+        // The pooled block is allocated again, so it is not handed out twice
+        assert_ne!(arena.allocate(16, 8), Some(pooled));
+
+        let other = Arena::new(256).unwrap();
+        assert!(arena.restore(&other.snapshot()).is_err());
+    }
+
+    #[test]
+    fn test_arena_self_test() {
+        assert_eq!(Arena::self_test(), Ok(()));
+    }
 }
@@ -0,0 +1,392 @@
+// Compact tag-length-value encoding of runtime values for snapshots
+// Every value starts with a one-byte tag. Integers are zigzag varints, floats
+// are 8 little-endian bytes, and strings, sequences and objects carry a
+// varint length followed by their contents
+
+use crate::object::{ClassInstance, Value};
+use thiserror::Error;
+
+const TAG_NONE: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_LIST: u8 = 6;
+const TAG_ARRAY: u8 = 7;
+const TAG_OBJECT: u8 = 8;
+const TAG_DICT: u8 = 9;
+const TAG_TUPLE: u8 = 10;
+const TAG_BYTES: u8 = 11;
+
+/// Nesting limit while encoding and decoding, so hostile input cannot
+/// exhaust the stack and every encoded value can be decoded again
+const MAX_DEPTH: usize = 128;
+
+/// Errors produced while encoding a value in the binary format
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EncodeError {
+    #[error("GC references are only meaningful in this process and cannot be encoded")]
+    Ref,
+    #[error("values nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+}
+
+/// Errors produced while decoding the binary value encoding
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DecodeError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("unknown value tag {0}")]
+    InvalidTag(u8),
+    #[error("varint does not fit in 64 bits")]
+    VarintOverflow,
+    #[error("invalid UTF-8 in string")]
+    InvalidUtf8,
+    #[error("values nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+}
+
+impl Value {
+    /// Encode the value in the compact binary format
+    /// Fails on GC references and on values nested too deep to decode
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut out = Vec::new();
+        encode(self, &mut out, 0)?;
+        Ok(out)
+    }
+
+    /// Decode a value produced by `to_bytes`, rejecting trailing bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Value, DecodeError> {
+        let mut decoder = Decoder { bytes, pos: 0 };
+        let value = decoder.value(0)?;
+        let remaining = bytes.len() - decoder.pos;
+        if remaining != 0 {
+            return Err(DecodeError::TrailingBytes(remaining));
+        }
+        Ok(value)
+    }
+}
+
+fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_varint(s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>, depth: usize) -> Result<(), EncodeError> {
+    if depth > MAX_DEPTH {
+        return Err(EncodeError::TooDeep);
+    }
+    match value {
+        Value::Ref(_) => return Err(EncodeError::Ref),
+        Value::None => out.push(TAG_NONE),
+        Value::Bool(false) => out.push(TAG_FALSE),
+        Value::Bool(true) => out.push(TAG_TRUE),
+        Value::Int(n) => {
+            out.push(TAG_INT);
+            // Zigzag so small negative numbers stay short
+            write_varint(((n << 1) ^ (n >> 63)) as u64, out);
+        }
+        Value::Float(f) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(TAG_STRING);
+            write_str(s, out);
+        }
+        Value::Bytes(bytes) => {
+            out.push(TAG_BYTES);
+            write_varint(bytes.len() as u64, out);
+            out.extend_from_slice(bytes);
+        }
+        Value::List(items) => encode_seq(TAG_LIST, items, out, depth)?,
+        Value::Array(items) => encode_seq(TAG_ARRAY, items, out, depth)?,
+        Value::Tuple(items) => encode_seq(TAG_TUPLE, items, out, depth)?,
+        Value::Dict(entries) => {
+            out.push(TAG_DICT);
+            write_varint(entries.len() as u64, out);
+            for (key, value) in entries {
+                encode(key, out, depth + 1)?;
+                encode(value, out, depth + 1)?;
+            }
+        }
+        Value::Object(instance) => {
+            out.push(TAG_OBJECT);
+            write_str(&instance.class_name, out);
+            let fields = instance.sorted_fields();
+            write_varint(fields.len() as u64, out);
+            for (name, value) in fields {
+                write_str(name, out);
+                encode(value, out, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_seq(
+    tag: u8,
+    items: &[Value],
+    out: &mut Vec<u8>,
+    depth: usize,
+) -> Result<(), EncodeError> {
+    out.push(tag);
+    write_varint(items.len() as u64, out);
+    for item in items {
+        encode(item, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.bytes.len() - self.pos {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(DecodeError::VarintOverflow);
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(DecodeError::VarintOverflow)
+    }
+
+    /// Read a length prefix, which can never exceed the bytes left since every
+    /// element takes at least one byte
+    fn length(&mut self) -> Result<usize, DecodeError> {
+        let len = self.varint()?;
+        if len > (self.bytes.len() - self.pos) as u64 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.length()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::TooDeep);
+        }
+        match self.byte()? {
+            TAG_NONE => Ok(Value::None),
+            TAG_FALSE => Ok(Value::Bool(false)),
+            TAG_TRUE => Ok(Value::Bool(true)),
+            TAG_INT => {
+                let n = self.varint()?;
+                Ok(Value::Int(((n >> 1) as i64) ^ -((n & 1) as i64)))
+            }
+            TAG_FLOAT => {
+                let bytes = self.take(8)?.try_into().unwrap();
+                Ok(Value::Float(f64::from_le_bytes(bytes)))
+            }
+            TAG_STRING => Ok(Value::String(self.string()?)),
+            TAG_BYTES => {
+                let len = self.length()?;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            tag @ (TAG_LIST | TAG_ARRAY | TAG_TUPLE) => {
+                let len = self.length()?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(match tag {
+                    TAG_LIST => Value::List(items),
+                    TAG_ARRAY => Value::Array(items),
+                    _ => Value::Tuple(items.into()),
+                })
+            }
+            TAG_DICT => {
+                let len = self.length()?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    let value = self.value(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Value::Dict(entries))
+            }
+            TAG_OBJECT => {
+                let mut instance = ClassInstance::new(self.string()?);
+                let len = self.length()?;
+                for _ in 0..len {
+                    let name = self.string()?;
+                    let value = self.value(depth + 1)?;
+                    instance.set_field(name, value);
+                }
+                Ok(Value::Object(instance))
+            }
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_round_trip() {
+        let point = ClassInstance::from_fields(
+            "Point".to_string(),
+            vec![
+                ("x".to_string(), Value::Int(-3)),
+                ("label".to_string(), Value::String("héllo".to_string())),
+            ],
+        );
+        let values = [
+            Value::None,
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Int(0),
+            Value::Int(-1),
+            Value::Int(i64::MAX),
+            Value::Int(i64::MIN),
+            Value::Float(-0.125),
+            Value::Float(f64::NAN),
+            Value::String(String::new()),
+            Value::Bytes(vec![0, 1, 0xff]),
+            Value::List(vec![Value::Int(1), Value::None]),
+            Value::Array(vec![Value::Float(1.0)]),
+            Value::Tuple(vec![Value::Bool(true), Value::None].into()),
+            Value::Dict(vec![(Value::String("k".to_string()), Value::Int(1))]),
+            Value::Object(point),
+        ];
+        for value in &values {
+            assert_eq!(
+                Value::from_bytes(&value.to_bytes().unwrap()).as_ref(),
+                Ok(value)
+            );
+        }
+        assert_eq!(Value::Int(-1).to_bytes(), Ok(vec![TAG_INT, 0x01]));
+
+        // Deep nesting mixing every container
+        let mut nested = Value::Int(7);
+        for depth in 0..100 {
+            nested = match depth % 3 {
+                0 => Value::List(vec![nested, Value::None]),
+                1 => Value::Array(vec![nested]),
+                _ => Value::Object(ClassInstance::from_fields(
+                    "Box".to_string(),
+                    vec![("inner".to_string(), nested)],
+                )),
+            };
+        }
+        assert_eq!(Value::from_bytes(&nested.to_bytes().unwrap()), Ok(nested));
+    }
+
+    #[test]
+    fn test_binary_encode_errors() {
+        let handle = crate::GarbageCollector::new().allocate_ref(8).unwrap();
+        let with_ref = Value::List(vec![Value::Int(1), Value::Ref(handle)]);
+        assert_eq!(with_ref.to_bytes(), Err(EncodeError::Ref));
+
+        let nest =
+            |levels: usize| (0..levels).fold(Value::Int(0), |inner, _| Value::List(vec![inner]));
+        // The deepest value that encodes also decodes
+        let deepest = nest(MAX_DEPTH);
+        assert_eq!(Value::from_bytes(&deepest.to_bytes().unwrap()), Ok(deepest));
+        assert_eq!(nest(MAX_DEPTH + 1).to_bytes(), Err(EncodeError::TooDeep));
+        assert_eq!(nest(200).to_bytes(), Err(EncodeError::TooDeep));
+    }
+
+    #[test]
+    fn test_binary_malformed_input() {
+        assert_eq!(Value::from_bytes(&[]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(Value::from_bytes(&[42]), Err(DecodeError::InvalidTag(42)));
+        assert_eq!(
+            Value::from_bytes(&[TAG_STRING, 5, b'a']),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            Value::from_bytes(&[TAG_STRING, 1, 0xff]),
+            Err(DecodeError::InvalidUtf8)
+        );
+        assert_eq!(
+            Value::from_bytes(&[
+                TAG_INT, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f
+            ]),
+            Err(DecodeError::VarintOverflow)
+        );
+        assert_eq!(
+            Value::from_bytes(&[TAG_NONE, TAG_NONE]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+
+        let deep: Vec<u8> = [TAG_LIST, 1].repeat(MAX_DEPTH + 2);
+        assert_eq!(Value::from_bytes(&deep), Err(DecodeError::TooDeep));
+
+        // Every truncation of a valid encoding fails cleanly
+        let bytes = Value::List(vec![
+            Value::String("abc".to_string()),
+            Value::Float(1.5),
+            Value::Object(ClassInstance::new("T".to_string())),
+        ])
+        .to_bytes()
+        .unwrap();
+        for len in 0..bytes.len() {
+            assert!(Value::from_bytes(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_binary_random_input_does_not_panic() {
+        // xorshift so the test is deterministic without extra dependencies
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let len = (next() % 64) as usize;
+            // Bias towards valid tags so decoding gets past the first byte
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => (next() % 12) as u8,
+                    _ => next() as u8,
+                })
+                .collect();
+            let _ = Value::from_bytes(&bytes);
+        }
+    }
+}
@@ -0,0 +1,405 @@
+// CBOR (RFC 8949) encoding of runtime values for embedded interop
+// Objects are encoded as tag 27 ("object with class name") wrapping
+// [class_name, {field: value, ...}]
+
+use crate::object::{ClassInstance, Value};
+use thiserror::Error;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const TAG_OBJECT: u64 = 27;
+
+const SIMPLE_FALSE: u8 = 20;
+const SIMPLE_TRUE: u8 = 21;
+const SIMPLE_NULL: u8 = 22;
+const FLOAT_16: u8 = 25;
+const FLOAT_32: u8 = 26;
+const FLOAT_64: u8 = 27;
+
+/// Nesting limit while encoding and decoding, so hostile input cannot
+/// exhaust the stack and every encoded value can be decoded again
+const MAX_DEPTH: usize = 128;
+
+/// Errors produced while encoding values as CBOR or decoding CBOR input
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum CborError {
+    #[error("unexpected end of CBOR input")]
+    UnexpectedEof,
+    #[error("integer does not fit in i64")]
+    IntegerOverflow,
+    #[error("invalid UTF-8 in text string")]
+    InvalidUtf8,
+    #[error("unsupported CBOR item: {0}")]
+    Unsupported(&'static str),
+    #[error("malformed object encoding: {0}")]
+    InvalidObject(&'static str),
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+    #[error("GC references are only meaningful in this process and cannot be encoded")]
+    Ref,
+    #[error("values nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+}
+
+/// Encode a value as CBOR
+/// Arrays, tuples and lists all encode as CBOR arrays and decode back as lists
+/// Fails on GC references and on values nested too deep to decode
+pub fn value_to_cbor(value: &Value) -> Result<Vec<u8>, CborError> {
+    let mut out = Vec::new();
+    encode(value, &mut out, 0)?;
+    Ok(out)
+}
+
+/// Decode a single CBOR item into a value, rejecting trailing bytes
+pub fn value_from_cbor(bytes: &[u8]) -> Result<Value, CborError> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    let value = decoder.value(0)?;
+    let remaining = bytes.len() - decoder.pos;
+    if remaining != 0 {
+        return Err(CborError::TrailingBytes(remaining));
+    }
+    Ok(value)
+}
+
+/// Write a major type with its argument using the smallest encoding
+fn write_head(major: u8, arg: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+fn write_text(s: &str, out: &mut Vec<u8>) {
+    write_head(MAJOR_TEXT, s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>, depth: usize) -> Result<(), CborError> {
+    if depth > MAX_DEPTH {
+        return Err(CborError::TooDeep);
+    }
+    match value {
+        Value::Int(n) if *n >= 0 => write_head(MAJOR_UNSIGNED, *n as u64, out),
+        // Negative integers are stored as -1 - n, which is the bitwise complement
+        Value::Int(n) => write_head(MAJOR_NEGATIVE, !(*n as u64), out),
+        Value::Float(f) => {
+            out.push((MAJOR_SIMPLE << 5) | FLOAT_64);
+            out.extend_from_slice(&f.to_bits().to_be_bytes());
+        }
+        Value::Bool(false) => out.push((MAJOR_SIMPLE << 5) | SIMPLE_FALSE),
+        Value::Bool(true) => out.push((MAJOR_SIMPLE << 5) | SIMPLE_TRUE),
+        Value::None => out.push((MAJOR_SIMPLE << 5) | SIMPLE_NULL),
+        Value::Ref(_) => return Err(CborError::Ref),
+        Value::String(s) => write_text(s, out),
+        Value::Bytes(bytes) => {
+            write_head(MAJOR_BYTES, bytes.len() as u64, out);
+            out.extend_from_slice(bytes);
+        }
+        Value::List(items) | Value::Array(items) => encode_array(items, out, depth)?,
+        Value::Tuple(items) => encode_array(items, out, depth)?,
+        Value::Dict(entries) => {
+            write_head(MAJOR_MAP, entries.len() as u64, out);
+            for (key, value) in entries {
+                encode(key, out, depth + 1)?;
+                encode(value, out, depth + 1)?;
+            }
+        }
+        Value::Object(instance) => {
+            write_head(MAJOR_TAG, TAG_OBJECT, out);
+            write_head(MAJOR_ARRAY, 2, out);
+            write_text(&instance.class_name, out);
+
+            // Sort fields so the encoding is deterministic
+            let mut fields: Vec<_> = instance.fields.iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            write_head(MAJOR_MAP, fields.len() as u64, out);
+            for (name, value) in fields {
+                write_text(name, out);
+                encode(value, out, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_array(items: &[Value], out: &mut Vec<u8>, depth: usize) -> Result<(), CborError> {
+    write_head(MAJOR_ARRAY, items.len() as u64, out);
+    for item in items {
+        encode(item, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CborError> {
+        let end = self.pos.checked_add(len).ok_or(CborError::UnexpectedEof)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(CborError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, CborError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Read the argument that follows an initial byte's additional info
+    fn argument(&mut self, info: u8) -> Result<u64, CborError> {
+        match info {
+            0..=23 => Ok(info as u64),
+            24 => Ok(self.byte()? as u64),
+            25 => Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64),
+            26 => Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64),
+            27 => Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap())),
+            31 => Err(CborError::Unsupported("indefinite-length item")),
+            _ => Err(CborError::Unsupported("reserved additional information")),
+        }
+    }
+
+    /// Read a length argument, rejecting lengths larger than the remaining input
+    fn length(&mut self, info: u8) -> Result<usize, CborError> {
+        let len = self.argument(info)?;
+        if len > (self.bytes.len() - self.pos) as u64 {
+            return Err(CborError::UnexpectedEof);
+        }
+        Ok(len as usize)
+    }
+
+    fn text(&mut self, info: u8) -> Result<String, CborError> {
+        let len = self.length(info)?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| CborError::InvalidUtf8)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, CborError> {
+        if depth > MAX_DEPTH {
+            return Err(CborError::TooDeep);
+        }
+        let initial = self.byte()?;
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+
+        match major {
+            MAJOR_UNSIGNED => {
+                let n = self.argument(info)?;
+                i64::try_from(n)
+                    .map(Value::Int)
+                    .map_err(|_| CborError::IntegerOverflow)
+            }
+            MAJOR_NEGATIVE => {
+                let n = self.argument(info)?;
+                i64::try_from(n)
+                    .map(|n| Value::Int(-1 - n))
+                    .map_err(|_| CborError::IntegerOverflow)
+            }
+            MAJOR_BYTES => {
+                let len = self.length(info)?;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            MAJOR_TEXT => self.text(info).map(Value::String),
+            MAJOR_ARRAY => {
+                let len = self.length(info)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::List(items))
+            }
+            MAJOR_MAP => {
+                let len = self.length(info)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    let value = self.value(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Value::Dict(entries))
+            }
+            MAJOR_TAG => match self.argument(info)? {
+                TAG_OBJECT => self.object(depth),
+                _ => Err(CborError::Unsupported("unknown tag")),
+            },
+            _ => self.simple(info),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, CborError> {
+        if self.byte()? != (MAJOR_ARRAY << 5) | 2 {
+            return Err(CborError::InvalidObject("expected [class_name, fields]"));
+        }
+
+        let initial = self.byte()?;
+        if initial >> 5 != MAJOR_TEXT {
+            return Err(CborError::InvalidObject("class name must be a text string"));
+        }
+        let mut instance = ClassInstance::new(self.text(initial & 0x1f)?);
+
+        let initial = self.byte()?;
+        if initial >> 5 != MAJOR_MAP {
+            return Err(CborError::InvalidObject("fields must be a map"));
+        }
+        let len = self.length(initial & 0x1f)?;
+        for _ in 0..len {
+            let initial = self.byte()?;
+            if initial >> 5 != MAJOR_TEXT {
+                return Err(CborError::InvalidObject("field names must be text strings"));
+            }
+            let name = self.text(initial & 0x1f)?;
+            let value = self.value(depth + 1)?;
+            instance.set_field(name, value);
+        }
+
+        Ok(Value::Object(instance))
+    }
+
+    fn simple(&mut self, info: u8) -> Result<Value, CborError> {
+        match info {
+            SIMPLE_FALSE => Ok(Value::Bool(false)),
+            SIMPLE_TRUE => Ok(Value::Bool(true)),
+            SIMPLE_NULL => Ok(Value::None),
+            FLOAT_16 => {
+                let bits = u16::from_be_bytes(self.take(2)?.try_into().unwrap());
+                Ok(Value::Float(half_to_f64(bits)))
+            }
+            FLOAT_32 => {
+                let bits = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+                Ok(Value::Float(f32::from_bits(bits) as f64))
+            }
+            FLOAT_64 => {
+                let bits = u64::from_be_bytes(self.take(8)?.try_into().unwrap());
+                Ok(Value::Float(f64::from_bits(bits)))
+            }
+            _ => Err(CborError::Unsupported("simple value")),
+        }
+    }
+}
+
+/// Convert an IEEE 754 half-precision float to f64
+fn half_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    };
+    sign * magnitude
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let mut point = ClassInstance::new("Point".to_string());
+        point.set_field("x".to_string(), Value::Int(-7));
+        point.set_field("y".to_string(), Value::Float(2.5));
+        point.set_field("label".to_string(), Value::String("origin".to_string()));
+
+        let value = Value::List(vec![
+            Value::Int(0),
+            Value::Int(i64::MAX),
+            Value::Int(i64::MIN),
+            Value::Float(-0.125),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::None,
+            Value::String("héllo".to_string()),
+            Value::Bytes(vec![0, 0xff]),
+            Value::List(vec![Value::Int(1), Value::List(vec![])]),
+            Value::Object(point),
+        ]);
+
+        let bytes = value_to_cbor(&value).unwrap();
+        assert_eq!(value_from_cbor(&bytes), Ok(value));
+    }
+
+    #[test]
+    fn test_cbor_compact_integers() {
+        assert_eq!(value_to_cbor(&Value::Int(10)), Ok(vec![0x0a]));
+        assert_eq!(value_to_cbor(&Value::Int(23)), Ok(vec![0x17]));
+        assert_eq!(value_to_cbor(&Value::Int(24)), Ok(vec![0x18, 0x18]));
+        assert_eq!(value_to_cbor(&Value::Int(1000)), Ok(vec![0x19, 0x03, 0xe8]));
+        assert_eq!(value_to_cbor(&Value::Int(-1)), Ok(vec![0x20]));
+        assert_eq!(value_to_cbor(&Value::Int(-100)), Ok(vec![0x38, 0x63]));
+    }
+
+    #[test]
+    fn test_cbor_malformed_input() {
+        assert_eq!(value_from_cbor(&[]), Err(CborError::UnexpectedEof));
+        // Text string claiming more bytes than are present
+        assert_eq!(
+            value_from_cbor(&[0x65, b'a']),
+            Err(CborError::UnexpectedEof)
+        );
+        assert_eq!(
+            value_from_cbor(&[0x01, 0x02]),
+            Err(CborError::TrailingBytes(1))
+        );
+        // Half-precision 1.5 from another encoder
+        assert_eq!(value_from_cbor(&[0xf9, 0x3e, 0x00]), Ok(Value::Float(1.5)));
+        // Undefined has no value counterpart
+        assert_eq!(
+            value_from_cbor(&[0xf7]),
+            Err(CborError::Unsupported("simple value"))
+        );
+    }
+
+    #[test]
+    fn test_cbor_encode_errors() {
+        let mut gc = crate::gc::GarbageCollector::new();
+        let handle = gc.allocate_ref(8).unwrap();
+        let with_ref = Value::List(vec![Value::Int(1), Value::Ref(handle)]);
+        assert_eq!(value_to_cbor(&with_ref), Err(CborError::Ref));
+
+        let nest =
+            |levels: usize| (0..levels).fold(Value::Int(0), |inner, _| Value::List(vec![inner]));
+        // The deepest value that encodes also decodes
+        let deepest = nest(MAX_DEPTH);
+        assert_eq!(
+            value_from_cbor(&value_to_cbor(&deepest).unwrap()),
+            Ok(deepest)
+        );
+        assert_eq!(value_to_cbor(&nest(MAX_DEPTH + 1)), Err(CborError::TooDeep));
+    }
+
+    #[test]
+    fn test_cbor_rejects_deep_nesting() {
+        // One-element arrays nested far past the limit must fail, not overflow the stack
+        let deep = vec![0x81; 1_000_000];
+        assert_eq!(value_from_cbor(&deep), Err(CborError::TooDeep));
+        let mut maps = [0xa1, 0x00].repeat(MAX_DEPTH + 1);
+        maps.push(0x00);
+        assert_eq!(value_from_cbor(&maps), Err(CborError::TooDeep));
+    }
+}
@@ -1,12 +1,66 @@
 // Garbage Collector for Pain runtime (dev profile)
 // Simple mark-and-sweep GC implementation
 
-use std::collections::{HashMap, HashSet};
+use alloc::alloc::{alloc, dealloc};
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::mem::{align_of, size_of};
+use core::time::Duration;
+use hashbrown::{HashMap, HashSet};
 
-/// GC-managed object header
+/// Values that hold pointers or handles to GC-managed objects
+/// The collector calls `trace` and `trace_refs` to find the objects a
+/// reachable value keeps alive
+pub trait Traceable {
+    /// Report the data pointer of every GC object this value references
+    fn trace(&self, _tracer: &mut dyn FnMut(*mut u8)) {}
+
+    /// Report every `GcRef` handle this value holds
+    fn trace_refs(&self, _tracer: &mut dyn FnMut(GcRef)) {}
+}
+
+/// Type-erased `Traceable::trace` and `trace_refs` for the value stored in an object's data
+type TraceFn = unsafe fn(*mut u8, &mut dyn FnMut(*mut u8), &mut dyn FnMut(GcRef));
+
+unsafe fn trace_as<T: Traceable>(
+    data_ptr: *mut u8,
+    tracer: &mut dyn FnMut(*mut u8),
+    ref_tracer: &mut dyn FnMut(GcRef),
+) {
+    let value = &*(data_ptr as *const T);
+    value.trace(tracer);
+    value.trace_refs(ref_tracer);
+}
+
+/// Callback run with an object's data pointer just before the object is freed
+pub type Finalizer = Box<dyn FnOnce(*mut u8)>;
+
+/// Collector lifecycle event delivered to `GarbageCollector::on_event` callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcEvent {
+    /// An allocation found a threshold reached and is about to collect
+    ThresholdExceeded,
+    CollectionStarted,
+    /// `duration` is wall time from the start of the collection, including the
+    /// mutator time between incremental steps; it is zero without `std`
+    CollectionFinished {
+        freed_bytes: usize,
+        freed_objects: usize,
+        duration: Duration,
+    },
+}
+
+/// GC-managed object header, stored in front of the object's data
+/// This is the only copy of the mark and pin bits; the collector reads and writes them in place
 #[derive(Debug)]
 struct GcHeader {
     marked: bool,
+    pinned: bool, // Set by `GcObject::mark`, consumed by the next collection
     size: usize,
     // Future: type info, weak refs, etc.
 }
@@ -31,16 +85,18 @@ impl GcObject {
         unsafe { (*self.header).size }
     }
 
-    /// Mark this object as reachable
+    /// Mark this object as reachable, keeping it and everything it references
+    /// alive through the next collection even if it is not rooted
+    /// A mark made during an incremental cycle is traced before that cycle ends
     pub fn mark(&self) {
         unsafe {
-            (*self.header).marked = true;
+            (*self.header).pinned = true;
         }
     }
 
-    /// Check if object is marked
+    /// Check if object is marked, by `mark` or by a collection in progress
     pub fn is_marked(&self) -> bool {
-        unsafe { (*self.header).marked }
+        unsafe { (*self.header).marked || (*self.header).pinned }
     }
 }
 
@@ -50,12 +106,233 @@ impl Drop for GcObject {
     }
 }
 
+/// Stable handle to a GC object, resolved through the collector's handle table
+/// Handles stay valid if the object is moved; only the table entry changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GcRef {
+    index: usize,
+}
+
+impl GcRef {
+    /// Resolve to the object's current data pointer, or None once it was collected
+    pub fn get(&self, gc: &GarbageCollector) -> Option<*mut u8> {
+        gc.handles.get(self.index).copied().flatten()
+    }
+
+    /// Get the handle's slot in the collector's handle table
+    #[cfg(feature = "std")]
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Weak reference to a GC object that does not keep it alive
+/// Upgrading fails once the object has been swept, even if its address is reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Weak {
+    ptr: *mut u8,
+    generation: u64,
+}
+
+/// Default ratio of the byte threshold to the bytes live after a collection
+const DEFAULT_GROWTH_FACTOR: f64 = 2.0;
+
+/// Objects marked between deadline checks in `collect_within`
+#[cfg(feature = "std")]
+const DEADLINE_CHECK_INTERVAL: usize = 64;
+
+/// Result of a time-bounded collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectOutcome {
+    Completed,
+    /// The budget ran out while marking; nothing was freed yet
+    Interrupted,
+}
+
+/// Minor collections run between two major collections in generational mode
+const MAJOR_INTERVAL: usize = 8;
+
+/// Generation bookkeeping for a collector in generational mode
+/// Objects not in the young set belong to the old generation
+#[derive(Default)]
+struct Generations {
+    young: HashSet<*mut u8>,
+    remembered: HashSet<*mut u8>, // Old objects with references to young ones
+    young_bytes: usize,
+    minor_collections: usize, // Since the last major collection
+}
+
+/// Clear a tracked object's mark, returning whether it was marked
+fn unmark(data_ptr: *mut u8) -> bool {
+    unsafe {
+        let header = header(data_ptr);
+        let marked = (*header).marked;
+        (*header).marked = false;
+        marked
+    }
+}
+
+/// Clear the pins set through `GcObject::mark` on the given objects and
+/// return those objects, to be marked as roots
+fn take_pins(objects: impl Iterator<Item = *mut u8>) -> Vec<*mut u8> {
+    objects
+        .filter(|&ptr| unsafe {
+            let header = header(ptr);
+            let pinned = (*header).pinned;
+            (*header).pinned = false;
+            pinned
+        })
+        .collect()
+}
+
+/// Get the header in front of a tracked object's data
+fn header(data_ptr: *mut u8) -> *mut GcHeader {
+    unsafe { data_ptr.sub(size_of::<GcHeader>()) as *mut GcHeader }
+}
+
+/// Guard for a root registered with `GarbageCollector::root`
+/// Dropping it unregisters the root, like `remove_root`
+pub struct Root {
+    roots: Rc<RefCell<HashSet<*mut u8>>>,
+    ptr: *mut u8,
+}
+
+impl Root {
+    /// Get the rooted data pointer
+    pub fn ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        self.roots.borrow_mut().remove(&self.ptr);
+    }
+}
+
+/// Garbage collection scheme a `Runtime` manages objects with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GcStrategy {
+    /// No collector; memory comes from the arena only
+    None,
+    #[default]
+    MarkSweep,
+    Generational,
+}
+
+impl GcStrategy {
+    /// Build the collector for this strategy with a byte `threshold`
+    pub fn build(self, threshold: usize) -> Option<Box<dyn Collector>> {
+        match self {
+            GcStrategy::None => None,
+            GcStrategy::MarkSweep => Some(Box::new(GarbageCollector::with_threshold(threshold))),
+            GcStrategy::Generational => {
+                Some(Box::new(GarbageCollector::with_generational(threshold)))
+            }
+        }
+    }
+}
+
+/// Collector interface a `Runtime` drives, so the GC implementation is pluggable
+pub trait Collector {
+    /// Run a collection
+    fn collect(&mut self);
+
+    /// Run a collection and return the bytes it freed
+    fn force_collect(&mut self) -> usize;
+
+    /// Get (allocated bytes, tracked objects, objects live after the last collection)
+    fn stats(&self) -> (usize, usize, usize);
+
+    /// Get the number of collections run so far
+    fn collection_count(&self) -> usize;
+
+    /// Get the duration of the most recent collection
+    fn last_pause(&self) -> Duration;
+
+    /// Register a root pointer
+    fn add_root(&mut self, ptr: *mut u8);
+
+    /// Unregister a root pointer
+    fn remove_root(&mut self, ptr: *mut u8);
+
+    /// Unregister every root pointer for which `keep` returns false
+    fn retain_roots(&mut self, keep: &mut dyn FnMut(*mut u8) -> bool);
+
+    /// Get the number of registered root pointers
+    fn root_count(&self) -> usize;
+}
+
+impl Collector for GarbageCollector {
+    fn collect(&mut self) {
+        GarbageCollector::collect(self);
+    }
+
+    fn force_collect(&mut self) -> usize {
+        GarbageCollector::force_collect(self)
+    }
+
+    fn stats(&self) -> (usize, usize, usize) {
+        GarbageCollector::stats(self)
+    }
+
+    fn collection_count(&self) -> usize {
+        GarbageCollector::collection_count(self)
+    }
+
+    fn last_pause(&self) -> Duration {
+        GarbageCollector::last_pause(self)
+    }
+
+    fn add_root(&mut self, ptr: *mut u8) {
+        GarbageCollector::add_root(self, ptr);
+    }
+
+    fn remove_root(&mut self, ptr: *mut u8) {
+        GarbageCollector::remove_root(self, ptr);
+    }
+
+    fn retain_roots(&mut self, keep: &mut dyn FnMut(*mut u8) -> bool) {
+        GarbageCollector::retain_roots(self, keep);
+    }
+
+    fn root_count(&self) -> usize {
+        GarbageCollector::root_count(self)
+    }
+}
+
 /// Garbage Collector - mark-and-sweep implementation
+/// Marks are cleared after every collection. Pins set through `GcObject::mark`
+/// live in a separate header bit and keep the object for the next collection
 pub struct GarbageCollector {
-    objects: HashMap<*mut u8, (GcHeader, usize)>, // data_ptr -> (header, size)
-    roots: HashSet<*mut u8>,                      // Root pointers (variables, stack, etc.)
+    objects: HashMap<*mut u8, Layout>, // data_ptr -> allocation layout
+    roots: Rc<RefCell<HashSet<*mut u8>>>, // Root pointers (variables, stack, etc.)
+    references: HashMap<*mut u8, Vec<*mut u8>>, // data_ptr -> objects it references
+    tracers: HashMap<*mut u8, TraceFn>, // data_ptr -> tracer of its contents
+    finalizers: HashMap<*mut u8, Finalizer>, // data_ptr -> callback run when swept
+    weak_generations: HashMap<*mut u8, u64>, // data_ptr -> generation of its weak refs
+    next_generation: u64,
+    generations: Option<Generations>, // Set in generational mode
+    traced_objects: usize,            // Objects marked over all collections
+    gray: Option<Vec<*mut u8>>,       // Worklist of an incremental cycle in progress
     total_allocated: usize,
-    threshold: usize, // GC threshold in bytes
+    threshold: usize,                // GC threshold in bytes
+    initial_threshold: usize,        // Lower bound when the threshold adapts
+    growth_factor: f64,              // Threshold as a multiple of live bytes after a collection
+    object_threshold: usize,         // GC threshold in tracked object count
+    initial_object_threshold: usize, // Lower bound when the object threshold adapts
+    collection_count: usize,
+    last_pause: Duration, // Duration of the most recent collection
+    live_objects: usize,  // Objects that survived the last collection
+    freed: Vec<*mut u8>,  // Data pointers freed by the last collection
+    freed_bytes: usize,
+    #[cfg(feature = "std")]
+    cycle_started: Option<std::time::Instant>,
+    listeners: Vec<Box<dyn FnMut(GcEvent)>>,
+    handles: Vec<Option<*mut u8>>, // GcRef index -> current data_ptr
+    handle_of: HashMap<*mut u8, usize>, // data_ptr -> GcRef index
+    #[cfg(test)]
+    failing_allocs: usize, // Upcoming allocations that simulate out of memory
 }
 
 impl GarbageCollector {
@@ -66,43 +343,91 @@ impl GarbageCollector {
 
     /// Create a new GC with custom threshold
     pub fn with_threshold(threshold: usize) -> Self {
+        Self::with_thresholds(threshold, usize::MAX)
+    }
+
+    /// Create a new GC that collects when either the allocated bytes reach
+    /// `threshold` or the number of tracked objects reaches `object_threshold`
+    pub fn with_thresholds(threshold: usize, object_threshold: usize) -> Self {
         Self {
             objects: HashMap::new(),
-            roots: HashSet::new(),
+            roots: Rc::new(RefCell::new(HashSet::new())),
+            references: HashMap::new(),
+            tracers: HashMap::new(),
+            finalizers: HashMap::new(),
+            weak_generations: HashMap::new(),
+            next_generation: 1,
+            generations: None,
+            traced_objects: 0,
+            gray: None,
             total_allocated: 0,
             threshold,
+            initial_threshold: threshold,
+            growth_factor: DEFAULT_GROWTH_FACTOR,
+            object_threshold,
+            initial_object_threshold: object_threshold,
+            collection_count: 0,
+            last_pause: Duration::ZERO,
+            live_objects: 0,
+            freed: Vec::new(),
+            freed_bytes: 0,
+            #[cfg(feature = "std")]
+            cycle_started: None,
+            listeners: Vec::new(),
+            handles: Vec::new(),
+            handle_of: HashMap::new(),
+            #[cfg(test)]
+            failing_allocs: 0,
+        }
+    }
+
+    /// Create a generational GC: objects start young and are promoted to the
+    /// old generation when they survive a collection. A collection runs when
+    /// the young generation reaches `threshold` bytes, and mostly scans only
+    /// the young objects
+    pub fn with_generational(threshold: usize) -> Self {
+        Self {
+            generations: Some(Generations::default()),
+            ..Self::with_threshold(threshold)
         }
     }
 
     /// Allocate a new GC-managed object
     pub fn allocate(&mut self, size: usize) -> Option<GcObject> {
+        let (pressure, object_pressure) = match &self.generations {
+            Some(generations) => (generations.young_bytes, generations.young.len()),
+            None => (self.total_allocated, self.objects.len()),
+        };
         // Check if we need to run GC
-        if self.total_allocated >= self.threshold {
+        if pressure >= self.threshold || object_pressure >= self.object_threshold {
+            self.emit(GcEvent::ThresholdExceeded);
             self.collect();
         }
 
         // Allocate header + data
-        let header_size = std::mem::size_of::<GcHeader>();
+        let header_size = size_of::<GcHeader>();
         let align = 8;
         let total_size = header_size + size;
         let aligned_size = (total_size + align - 1) & !(align - 1);
 
-        unsafe {
-            let layout = std::alloc::Layout::from_size_align(aligned_size, align).ok()?;
-            let ptr = std::alloc::alloc(layout);
+        let layout = Layout::from_size_align(aligned_size, align).ok()?;
+        let mut ptr = self.alloc_raw(layout);
+        if ptr.is_null() {
+            // Try GC and retry
+            self.collect();
+            ptr = self.alloc_raw(layout);
             if ptr.is_null() {
-                // Try GC and retry
-                self.collect();
-                let ptr = std::alloc::alloc(layout);
-                if ptr.is_null() {
-                    return None;
-                }
+                return None;
             }
+        }
 
+        unsafe {
             // Initialize header
+            // Objects allocated during an incremental cycle are marked so it keeps them
             let header_ptr = ptr as *mut GcHeader;
             (*header_ptr) = GcHeader {
-                marked: false,
+                marked: self.gray.is_some(),
+                pinned: false,
                 size,
             };
 
@@ -110,17 +435,12 @@ impl GarbageCollector {
             let data_ptr = ptr.add(header_size);
 
             // Track object
-            self.objects.insert(
-                data_ptr,
-                (
-                    GcHeader {
-                        marked: false,
-                        size,
-                    },
-                    aligned_size,
-                ),
-            );
+            self.objects.insert(data_ptr, layout);
             self.total_allocated += aligned_size;
+            if let Some(generations) = self.generations.as_mut() {
+                generations.young.insert(data_ptr);
+                generations.young_bytes += aligned_size;
+            }
 
             Some(GcObject {
                 header: header_ptr,
@@ -129,90 +449,594 @@ impl GarbageCollector {
         }
     }
 
+    /// Get memory for an object from the global allocator
+    fn alloc_raw(&mut self, layout: Layout) -> *mut u8 {
+        #[cfg(test)]
+        if self.failing_allocs > 0 {
+            self.failing_allocs -= 1;
+            return core::ptr::null_mut();
+        }
+        unsafe { alloc(layout) }
+    }
+
+    /// Allocate a new GC-managed object whose finalizer runs once, when the
+    /// object is swept
+    pub fn allocate_with_finalizer(
+        &mut self,
+        size: usize,
+        finalizer: Finalizer,
+    ) -> Option<GcObject> {
+        let object = self.allocate(size)?;
+        self.finalizers.insert(object.data_ptr(), finalizer);
+        Some(object)
+    }
+
+    /// Allocate a new GC-managed object and return a stable handle to it
+    /// Handle slots are not reused, so a handle to a collected object stays dead
+    pub fn allocate_ref(&mut self, size: usize) -> Option<GcRef> {
+        let data_ptr = self.allocate(size)?.data_ptr();
+        let index = self.handles.len();
+        self.handles.push(Some(data_ptr));
+        self.handle_of.insert(data_ptr, index);
+        Some(GcRef { index })
+    }
+
+    /// Move a tracked object to a fresh allocation and return its new data
+    /// pointer, or None if `data_ptr` is not tracked or memory ran out
+    ///
+    /// Every table of the collector follows the move, so `GcRef` handles,
+    /// roots, references, finalizers and tracers keep working. Raw pointers to
+    /// the old address held elsewhere, including inside traced data, `Root`
+    /// guards and `Weak` references, are not updated
+    ///
+    /// # Safety
+    /// No reference into the object's data may be live across the call, and
+    /// the old address must not be used once the object has moved
+    pub unsafe fn relocate(&mut self, data_ptr: *mut u8) -> Option<*mut u8> {
+        let layout = *self.objects.get(&data_ptr)?;
+        let new_base = self.alloc_raw(layout);
+        if new_base.is_null() {
+            return None;
+        }
+        let new_ptr = unsafe {
+            let old_base = data_ptr.sub(size_of::<GcHeader>());
+            core::ptr::copy_nonoverlapping(old_base, new_base, layout.size());
+            dealloc(old_base, layout);
+            new_base.add(size_of::<GcHeader>())
+        };
+
+        let rekey = |ptr: &mut *mut u8| {
+            if *ptr == data_ptr {
+                *ptr = new_ptr;
+            }
+        };
+        self.objects.remove(&data_ptr);
+        self.objects.insert(new_ptr, layout);
+        {
+            let mut roots = self.roots.borrow_mut();
+            if roots.remove(&data_ptr) {
+                roots.insert(new_ptr);
+            }
+        }
+        if let Some(children) = self.references.remove(&data_ptr) {
+            self.references.insert(new_ptr, children);
+        }
+        for children in self.references.values_mut() {
+            children.iter_mut().for_each(rekey);
+        }
+        if let Some(trace) = self.tracers.remove(&data_ptr) {
+            self.tracers.insert(new_ptr, trace);
+        }
+        if let Some(finalizer) = self.finalizers.remove(&data_ptr) {
+            self.finalizers.insert(new_ptr, finalizer);
+        }
+        if let Some(generation) = self.weak_generations.remove(&data_ptr) {
+            self.weak_generations.insert(new_ptr, generation);
+        }
+        if let Some(generations) = self.generations.as_mut() {
+            if generations.young.remove(&data_ptr) {
+                generations.young.insert(new_ptr);
+            }
+            if generations.remembered.remove(&data_ptr) {
+                generations.remembered.insert(new_ptr);
+            }
+        }
+        if let Some(gray) = self.gray.as_mut() {
+            gray.iter_mut().for_each(rekey);
+        }
+        if let Some(index) = self.handle_of.remove(&data_ptr) {
+            self.handle_of.insert(new_ptr, index);
+            self.handles[index] = Some(new_ptr);
+        }
+        Some(new_ptr)
+    }
+
+    /// Create a weak reference to a tracked object
+    /// A pointer that is not a live object yields a weak reference that never upgrades
+    pub fn downgrade(&mut self, data_ptr: *mut u8) -> Weak {
+        if !self.objects.contains_key(&data_ptr) {
+            return Weak {
+                ptr: data_ptr,
+                generation: 0,
+            };
+        }
+        let next_generation = &mut self.next_generation;
+        let generation = *self.weak_generations.entry(data_ptr).or_insert_with(|| {
+            let generation = *next_generation;
+            *next_generation += 1;
+            generation
+        });
+        Weak {
+            ptr: data_ptr,
+            generation,
+        }
+    }
+
+    /// Resolve a weak reference, or None once its object has been swept
+    pub fn upgrade(&self, weak: &Weak) -> Option<*mut u8> {
+        (self.weak_generations.get(&weak.ptr) == Some(&weak.generation)).then_some(weak.ptr)
+    }
+
     /// Register a root pointer (variable, stack reference, etc.)
     pub fn add_root(&mut self, ptr: *mut u8) {
-        self.roots.insert(ptr);
+        self.roots.borrow_mut().insert(ptr);
+        if let Some(gray) = self.gray.as_mut() {
+            gray.push(ptr);
+        }
+    }
+
+    /// Register a root pointer that stays registered until the returned guard
+    /// is dropped
+    pub fn root(&mut self, ptr: *mut u8) -> Root {
+        self.add_root(ptr);
+        Root {
+            roots: Rc::clone(&self.roots),
+            ptr,
+        }
     }
 
     /// Unregister a root pointer
     pub fn remove_root(&mut self, ptr: *mut u8) {
-        self.roots.remove(&ptr);
+        self.roots.borrow_mut().remove(&ptr);
     }
 
-    /// Mark all reachable objects from roots
-    fn mark_phase(&mut self) {
-        // Reset all marks
-        for (_, (header, _)) in self.objects.iter_mut() {
-            header.marked = false;
-        }
+    /// Unregister every root pointer for which `keep` returns false
+    /// `Root` guards of removed roots stay valid and drop as no-ops
+    pub fn retain_roots(&mut self, mut keep: impl FnMut(*mut u8) -> bool) {
+        self.roots.borrow_mut().retain(|&ptr| keep(ptr));
+    }
+
+    /// Get the number of registered root pointers
+    pub fn root_count(&self) -> usize {
+        self.roots.borrow().len()
+    }
 
-        // Mark all roots and recursively mark their references
-        let mut to_mark: Vec<*mut u8> = self.roots.iter().copied().collect();
-        let mut marked = HashSet::new();
+    /// Record that `parent` holds a pointer to `child`, keeping `child` alive
+    /// for as long as `parent` is reachable
+    pub fn add_reference(&mut self, parent: *mut u8, child: *mut u8) {
+        self.references.entry(parent).or_default().push(child);
+        self.write_barrier(parent, child);
+    }
 
-        while let Some(ptr) = to_mark.pop() {
-            if marked.contains(&ptr) {
-                continue;
+    /// Tell the collector that a pointer to `new_child` was stored in `parent`
+    ///
+    /// Code that assigns a GC pointer into an object's traced data, such as a
+    /// `Value::Ref` field of a `set_traceable` object, must call this with the
+    /// handle's data pointer after the
+    /// store. Otherwise an incremental cycle that already scanned `parent`, or a
+    /// minor collection that skips old objects, can free `new_child`
+    pub fn write_barrier(&mut self, parent: *mut u8, new_child: *mut u8) {
+        if let Some(gray) = self.gray.as_mut() {
+            let parent_scanned =
+                self.objects.contains_key(&parent) && unsafe { (*header(parent)).marked };
+            if parent_scanned {
+                gray.push(new_child);
             }
-            marked.insert(ptr);
+        }
+        if let Some(generations) = self.generations.as_mut() {
+            if !generations.young.contains(&parent) && generations.young.contains(&new_child) {
+                generations.remembered.insert(parent);
+            }
+        }
+    }
 
-            // Mark this object
-            if let Some((header, _)) = self.objects.get_mut(&ptr) {
-                header.marked = true;
+    /// Conservatively scan a memory region for roots: every aligned word that
+    /// equals a tracked object's data pointer keeps that object alive through
+    /// the next collection, as if it had been marked
+    ///
+    /// Integers that happen to match an object's address keep it alive too.
+    /// Scan again before each collection; found objects are not rooted for good
+    ///
+    /// # Safety
+    /// `start..start + len` must be readable memory
+    pub unsafe fn scan_region(&mut self, start: *const u8, len: usize) {
+        let word = size_of::<usize>();
+        let end = start as usize + len;
+        let mut addr = start as usize + start.align_offset(align_of::<usize>());
+        while addr + word <= end {
+            let candidate = (addr as *const usize).read() as *mut u8;
+            if self.objects.contains_key(&candidate) {
+                match self.gray.as_mut() {
+                    Some(gray) => gray.push(candidate),
+                    None => (*header(candidate)).pinned = true,
+                }
+            }
+            addr += word;
+        }
+    }
+
+    /// Trace the `T` stored in an object's data whenever the object is marked,
+    /// so the objects it points to stay alive with it
+    ///
+    /// The `T` is not dropped when the object is swept
+    ///
+    /// # Safety
+    /// `data_ptr` must be the data pointer of an object of this collector that
+    /// holds an initialized `T` for as long as the object is tracked
+    pub unsafe fn set_traceable<T: Traceable>(&mut self, data_ptr: *mut u8) {
+        self.tracers.insert(data_ptr, trace_as::<T>);
+    }
+
+    /// Mark all reachable objects from roots
+    fn mark_phase(&mut self) {
+        // Mark all roots and externally marked objects, and recursively mark their references
+        let mut roots = take_pins(self.objects.keys().copied());
+        roots.extend(self.roots.borrow().iter().copied());
+        self.mark_from(&mut roots, false, usize::MAX);
+    }
 
-                // For now, we don't traverse object internals
-                // Future: scan object for pointers and add them to to_mark
+    /// Mark up to `budget` unmarked objects reachable from `to_mark`, returning
+    /// true once the worklist is empty
+    /// With `young_only`, objects outside the young generation are neither
+    /// marked nor traversed
+    fn mark_from(&mut self, to_mark: &mut Vec<*mut u8>, young_only: bool, budget: usize) -> bool {
+        let mut marked = 0;
+        while marked < budget {
+            let Some(ptr) = to_mark.pop() else {
+                return true;
+            };
+            if young_only && !self.is_young(ptr) {
+                continue;
+            }
+            if !self.objects.contains_key(&ptr) {
+                continue;
+            }
+            let header = header(ptr);
+            unsafe {
+                if (*header).marked {
+                    continue;
+                }
+                (*header).marked = true;
             }
+            marked += 1;
+            self.traced_objects += 1;
+            self.push_children(ptr, to_mark);
         }
+        to_mark.is_empty()
+    }
+
+    /// Push the objects `ptr` references, explicit and traced, onto `out`
+    fn push_children(&self, ptr: *mut u8, out: &mut Vec<*mut u8>) {
+        if let Some(children) = self.references.get(&ptr) {
+            out.extend(children.iter().copied());
+        }
+        if let Some(trace) = self.tracers.get(&ptr) {
+            let mut refs = Vec::new();
+            unsafe { trace(ptr, &mut |child| out.push(child), &mut |r| refs.push(r)) };
+            out.extend(refs.iter().filter_map(|r| r.get(self)));
+        }
+    }
+
+    fn is_young(&self, ptr: *mut u8) -> bool {
+        self.generations
+            .as_ref()
+            .is_some_and(|generations| generations.young.contains(&ptr))
     }
 
-    /// Sweep phase - free unmarked objects
+    /// Sweep phase - free unmarked objects and clear the marks of the rest
     fn sweep_phase(&mut self) {
         let mut to_remove = Vec::new();
-        let mut freed = 0;
 
-        for (data_ptr, (header, size)) in &self.objects {
-            if !header.marked {
-                to_remove.push(*data_ptr);
-                freed += size;
+        for &data_ptr in self.objects.keys() {
+            if !unmark(data_ptr) {
+                to_remove.push(data_ptr);
             }
         }
 
         for data_ptr in to_remove {
-            if let Some((_, total_size)) = self.objects.remove(&data_ptr) {
-                unsafe {
-                    // Calculate header size
-                    let header_size = std::mem::size_of::<GcHeader>();
-                    let align = 8;
-                    let aligned_size = header_size + total_size;
-                    let aligned_size = (aligned_size + align - 1) & !(align - 1);
-
-                    // Get pointer to start of allocation (header)
-                    let header_ptr = data_ptr.sub(header_size);
-
-                    let layout = std::alloc::Layout::from_size_align(aligned_size, 8)
-                        .expect("Invalid layout");
-                    std::alloc::dealloc(header_ptr, layout);
-                }
-                self.total_allocated -= total_size;
+            self.free_object(data_ptr);
+        }
+    }
+
+    /// Free a tracked object and drop everything the collector keeps about it
+    fn free_object(&mut self, data_ptr: *mut u8) {
+        if let Some(layout) = self.objects.remove(&data_ptr) {
+            if let Some(finalizer) = self.finalizers.remove(&data_ptr) {
+                finalizer(data_ptr);
+            }
+            self.freed.push(data_ptr);
+            self.freed_bytes += layout.size();
+            unsafe {
+                // Get pointer to start of allocation (header)
+                let header_ptr = data_ptr.sub(size_of::<GcHeader>());
+
+                // Free with exactly the layout it was allocated with
+                dealloc(header_ptr, layout);
+            }
+            self.total_allocated -= layout.size();
+            self.references.remove(&data_ptr);
+            self.tracers.remove(&data_ptr);
+            self.weak_generations.remove(&data_ptr);
+            if let Some(index) = self.handle_of.remove(&data_ptr) {
+                self.handles[index] = None;
+            }
+        }
+    }
+
+    /// Collect only the young generation, promoting its survivors
+    /// Old objects are assumed live and only the remembered ones are scanned,
+    /// for references into the young generation
+    fn minor_collect(&mut self) {
+        let Some(generations) = self.generations.as_mut() else {
+            return;
+        };
+        let remembered = core::mem::take(&mut generations.remembered);
+        generations.young_bytes = 0;
+
+        let young = generations.young.iter().copied();
+        let mut to_mark = take_pins(young);
+        to_mark.extend(
+            self.roots
+                .borrow()
+                .iter()
+                .copied()
+                .filter(|&ptr| self.is_young(ptr)),
+        );
+        for &parent in &remembered {
+            self.push_children(parent, &mut to_mark);
+        }
+        self.mark_from(&mut to_mark, true, usize::MAX);
+
+        let young = self
+            .generations
+            .as_mut()
+            .map(|generations| core::mem::take(&mut generations.young))
+            .unwrap_or_default();
+        for ptr in young {
+            if !unmark(ptr) {
+                self.free_object(ptr);
             }
         }
+        self.live_objects = self.objects.len();
     }
 
     /// Run garbage collection
+    /// In generational mode this is a minor collection, except for every
+    /// `MAJOR_INTERVAL`th collection which is a full one
     pub fn collect(&mut self) {
+        self.abandon_cycle();
+        let minor = match self.generations.as_mut() {
+            Some(generations) if generations.minor_collections < MAJOR_INTERVAL => {
+                generations.minor_collections += 1;
+                true
+            }
+            _ => false,
+        };
+
+        if minor {
+            self.start_collection();
+            self.minor_collect();
+            self.finish_collection();
+        } else {
+            self.collect_major();
+        }
+    }
+
+    /// Run garbage collection and return the data pointers it freed, in no
+    /// particular order
+    pub fn collect_freed(&mut self) -> Vec<*mut u8> {
+        self.collect();
+        core::mem::take(&mut self.freed)
+    }
+
+    /// Run a full collection over every object, in generational mode too
+    pub fn collect_major(&mut self) {
+        self.abandon_cycle();
+        self.start_collection();
         self.mark_phase();
+        self.finish_cycle();
+        self.finish_collection();
+    }
+
+    /// Run one bounded step of an incremental full collection, marking up to
+    /// `budget` objects, and return true when the step completed a cycle
+    ///
+    /// The first step starts a cycle from the current roots; later steps
+    /// continue it. Objects allocated or newly rooted or referenced through
+    /// `add_reference` while a cycle is in progress survive it
+    pub fn collect_step(&mut self, budget: usize) -> bool {
+        let mut gray = match self.gray.take() {
+            Some(gray) => gray,
+            None => {
+                self.start_collection();
+                let mut gray = take_pins(self.objects.keys().copied());
+                gray.extend(self.roots.borrow().iter().copied());
+                gray
+            }
+        };
+
+        loop {
+            if !self.mark_from(&mut gray, false, budget) {
+                self.gray = Some(gray);
+                return false;
+            }
+            // Objects pinned during the cycle are traced before it can end
+            gray = take_pins(self.objects.keys().copied());
+            if gray.is_empty() {
+                break;
+            }
+        }
+        self.finish_cycle();
+        self.finish_collection();
+        true
+    }
+
+    /// Run an incremental full collection for at most about `budget`, checking
+    /// the clock every `DEADLINE_CHECK_INTERVAL` marked objects
+    /// An interrupted collection keeps its progress and resumes on the next
+    /// call, or on `collect_step`
+    #[cfg(feature = "std")]
+    pub fn collect_within(&mut self, budget: Duration) -> CollectOutcome {
+        let started = std::time::Instant::now();
+        loop {
+            if self.collect_step(DEADLINE_CHECK_INTERVAL) {
+                return CollectOutcome::Completed;
+            }
+            if started.elapsed() >= budget {
+                return CollectOutcome::Interrupted;
+            }
+        }
+    }
+
+    /// Drop an incremental cycle in progress, clearing its partial marks so a
+    /// full collection does not mistake them for pins
+    fn abandon_cycle(&mut self) {
+        if self.gray.take().is_some() {
+            for &ptr in self.objects.keys() {
+                unmark(ptr);
+            }
+        }
+    }
+
+    /// Sweep after a full mark and reset generation state
+    fn finish_cycle(&mut self) {
         self.sweep_phase();
+        if let Some(generations) = self.generations.as_mut() {
+            generations.young.clear();
+            generations.remembered.clear();
+            generations.young_bytes = 0;
+            generations.minor_collections = 0;
+        }
+        self.live_objects = self.objects.len();
+    }
+
+    fn start_collection(&mut self) {
+        self.freed.clear();
+        self.freed_bytes = 0;
+        #[cfg(feature = "std")]
+        {
+            self.cycle_started = Some(std::time::Instant::now());
+        }
+        self.emit(GcEvent::CollectionStarted);
     }
 
-    /// Get memory statistics
+    fn finish_collection(&mut self) {
+        self.collection_count += 1;
+        // Leave room to grow past the live set so a large one does not
+        // trigger a collection on every allocation. In generational mode the
+        // thresholds apply to the young generation, so the old one must not
+        // push them up
+        let (live_bytes, live_objects) = match &self.generations {
+            Some(generations) => (generations.young_bytes, generations.young.len()),
+            None => (self.total_allocated, self.objects.len()),
+        };
+        let target = live_bytes as f64 * self.growth_factor;
+        self.threshold = self.initial_threshold.max(target as usize);
+        let object_target = live_objects as f64 * self.growth_factor;
+        self.object_threshold = self.initial_object_threshold.max(object_target as usize);
+        #[cfg(feature = "std")]
+        let duration = self
+            .cycle_started
+            .take()
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        #[cfg(not(feature = "std"))]
+        let duration = Duration::ZERO;
+        self.last_pause = duration;
+        self.emit(GcEvent::CollectionFinished {
+            freed_bytes: self.freed_bytes,
+            freed_objects: self.freed.len(),
+            duration,
+        });
+    }
+
+    fn emit(&mut self, event: GcEvent) {
+        for listener in &mut self.listeners {
+            listener(event);
+        }
+    }
+
+    /// Install a callback that receives every `GcEvent`
+    pub fn on_event(&mut self, listener: Box<dyn FnMut(GcEvent)>) {
+        self.listeners.push(listener);
+    }
+
+    /// Get memory statistics: (allocated bytes, tracked objects, objects live
+    /// after the last collection)
     pub fn stats(&self) -> (usize, usize, usize) {
-        let live_objects = self
-            .objects
-            .values()
-            .filter(|(header, _)| header.marked)
-            .count();
-        (self.total_allocated, self.objects.len(), live_objects)
+        (self.total_allocated, self.objects.len(), self.live_objects)
+    }
+
+    /// Render the object graph the collector sees in Graphviz DOT format
+    /// Nodes show each object's size and mark; roots are drawn as bold boxes.
+    /// Edges include both `add_reference` edges and traced references
+    pub fn dump_dot(&self) -> String {
+        let mut objects: Vec<*mut u8> = self.objects.keys().copied().collect();
+        objects.sort();
+        let roots = self.roots.borrow();
+
+        let mut out = String::from("digraph heap {\n");
+        for &ptr in &objects {
+            let header = unsafe { &*header(ptr) };
+            let marked = if header.marked { ", marked" } else { "" };
+            let style = if roots.contains(&ptr) {
+                ", shape=box, style=bold"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    \"{:p}\" [label=\"{:p}\\n{} bytes{}\"{}];",
+                ptr, ptr, header.size, marked, style
+            );
+        }
+
+        let mut children = Vec::new();
+        for &ptr in &objects {
+            children.clear();
+            self.push_children(ptr, &mut children);
+            for &child in &children {
+                let _ = writeln!(out, "    \"{:p}\" -> \"{:p}\";", ptr, child);
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Set how far the byte and object count thresholds grow past the bytes and
+    /// objects live after each collection; they never drop below their initial values
+    pub fn set_growth_factor(&mut self, factor: f64) -> Result<(), &'static str> {
+        if !(factor >= 1.0 && factor.is_finite()) {
+            return Err("Growth factor must be a finite number of at least 1.0");
+        }
+        self.growth_factor = factor;
+        Ok(())
+    }
+
+    /// Get the (byte, object count) collection thresholds
+    pub fn thresholds(&self) -> (usize, usize) {
+        (self.threshold, self.object_threshold)
+    }
+
+    /// Get the number of collections run so far
+    pub fn collection_count(&self) -> usize {
+        self.collection_count
+    }
+
+    /// Get the duration of the most recent collection, as reported in
+    /// `GcEvent::CollectionFinished`; zero before the first one
+    pub fn last_pause(&self) -> Duration {
+        self.last_pause
     }
 
     /// Force collection and return freed memory
@@ -272,4 +1096,659 @@ mod tests {
         assert_eq!(total, 1);
         assert_eq!(live, 1);
     }
+
+    #[test]
+    fn test_gc_traverses_references() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let a = gc.allocate(16).unwrap().data_ptr();
+        let b = gc.allocate(16).unwrap().data_ptr();
+        let c = gc.allocate(16).unwrap().data_ptr();
+        let _garbage = gc.allocate(16).unwrap();
+
+        // a -> b -> c, with only a rooted
+        gc.add_root(a);
+        gc.add_reference(a, b);
+        gc.add_reference(b, c);
+        gc.collect();
+
+        let (_, total, live) = gc.stats();
+        assert_eq!(total, 3);
+        assert_eq!(live, 3);
+
+        // Dropping the root frees the whole chain
+        gc.remove_root(a);
+        gc.collect();
+        assert_eq!(gc.stats(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_gc_finalizers_run_once_for_swept_objects() {
+        use alloc::rc::Rc;
+        use core::cell::Cell;
+
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let finalized = Rc::new(Cell::new(Vec::new()));
+        let record = |finalized: &Rc<Cell<Vec<*mut u8>>>| -> Finalizer {
+            let finalized = Rc::clone(finalized);
+            Box::new(move |ptr| {
+                let mut seen = finalized.take();
+                seen.push(ptr);
+                finalized.set(seen);
+            })
+        };
+
+        let kept = gc.allocate_with_finalizer(16, record(&finalized)).unwrap();
+        let dropped = gc.allocate_with_finalizer(16, record(&finalized)).unwrap();
+        gc.add_root(kept.data_ptr());
+
+        gc.collect();
+        gc.collect();
+        assert_eq!(finalized.take(), vec![dropped.data_ptr()]);
+
+        gc.remove_root(kept.data_ptr());
+        gc.collect();
+        gc.collect();
+        assert_eq!(finalized.take(), vec![kept.data_ptr()]);
+    }
+
+    #[test]
+    fn test_gc_weak_refs() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let rooted = gc.allocate(16).unwrap().data_ptr();
+        let unrooted = gc.allocate(16).unwrap().data_ptr();
+        gc.add_root(rooted);
+
+        let weak_rooted = gc.downgrade(rooted);
+        let weak_unrooted = gc.downgrade(unrooted);
+        assert_eq!(gc.upgrade(&weak_unrooted), Some(unrooted));
+
+        gc.collect();
+        assert_eq!(gc.upgrade(&weak_rooted), Some(rooted));
+        assert_eq!(gc.upgrade(&weak_unrooted), None);
+
+        // A new object at a recycled address does not revive old weak refs
+        gc.remove_root(rooted);
+        gc.collect();
+        let reused = (0..64)
+            .map(|_| gc.allocate(16).unwrap().data_ptr())
+            .find(|&ptr| ptr == rooted || ptr == unrooted);
+        if let Some(ptr) = reused {
+            gc.downgrade(ptr);
+        }
+        assert_eq!(gc.upgrade(&weak_rooted), None);
+        assert_eq!(gc.upgrade(&weak_unrooted), None);
+
+        let untracked = gc.downgrade(core::ptr::null_mut());
+        assert_eq!(gc.upgrade(&untracked), None);
+    }
+
+    #[test]
+    fn test_gc_generational_minor_collections() {
+        let mut gc = GarbageCollector::with_generational(1024 * 1024);
+        let root = gc.allocate(16).unwrap().data_ptr();
+        let child = gc.allocate(16).unwrap().data_ptr();
+        gc.add_root(root);
+        gc.add_reference(root, child);
+
+        // The first collection traces both and promotes them
+        gc.collect();
+        assert_eq!(gc.traced_objects, 2);
+
+        // Short-lived garbage is freed without rescanning the promoted objects
+        for _ in 0..5 {
+            for _ in 0..10 {
+                gc.allocate(32).unwrap();
+            }
+            gc.collect();
+            assert_eq!(gc.stats().1, 2);
+        }
+        assert_eq!(gc.traced_objects, 2);
+
+        // A young object referenced from an old one survives via the remembered set
+        let young = gc.allocate(16).unwrap().data_ptr();
+        gc.add_reference(child, young);
+        gc.collect();
+        assert_eq!(gc.traced_objects, 3);
+        assert_eq!(gc.stats().1, 3);
+
+        // Dead old objects are only reclaimed by a major collection
+        gc.remove_root(root);
+        gc.collect();
+        assert_eq!(gc.stats().1, 3);
+        gc.collect_major();
+        assert_eq!(gc.stats(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_gc_incremental_steps() {
+        // Two identical heaps: a rooted chain of 10 objects plus 5 garbage objects
+        let build = || {
+            let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+            let chain: Vec<_> = (0..10)
+                .map(|_| gc.allocate(16).unwrap().data_ptr())
+                .collect();
+            for pair in chain.windows(2) {
+                gc.add_reference(pair[0], pair[1]);
+            }
+            gc.add_root(chain[0]);
+            for _ in 0..5 {
+                gc.allocate(16).unwrap();
+            }
+            gc
+        };
+
+        let mut full = build();
+        full.collect();
+
+        let mut incremental = build();
+        let mut steps = 1;
+        while !incremental.collect_step(3) {
+            steps += 1;
+            if steps == 2 {
+                // Allocated mid-cycle, so it survives this cycle
+                incremental.allocate(16).unwrap();
+            }
+        }
+        assert!(steps >= 4);
+        assert_eq!(incremental.collection_count(), 1);
+
+        let (_, total, _) = incremental.stats();
+        assert_eq!(total, full.stats().1 + 1);
+        assert_eq!(full.stats().1, 10);
+
+        // The next cycle frees the mid-cycle allocation
+        while !incremental.collect_step(100) {}
+        assert_eq!(incremental.stats(), full.stats());
+    }
+
+    #[test]
+    fn test_gc_write_barrier() {
+        struct Node {
+            next: *mut u8,
+        }
+
+        impl Traceable for Node {
+            fn trace(&self, tracer: &mut dyn FnMut(*mut u8)) {
+                if !self.next.is_null() {
+                    tracer(self.next);
+                }
+            }
+        }
+
+        let new_node = |gc: &mut GarbageCollector| {
+            let ptr = gc.allocate(size_of::<Node>()).unwrap().data_ptr();
+            unsafe {
+                (ptr as *mut Node).write(Node {
+                    next: core::ptr::null_mut(),
+                });
+                gc.set_traceable::<Node>(ptr);
+            }
+            ptr
+        };
+
+        // Store into an already scanned object in the middle of an incremental cycle
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let parent = new_node(&mut gc);
+        let child = new_node(&mut gc);
+        let sibling = new_node(&mut gc);
+        gc.add_root(parent);
+        gc.add_reference(parent, sibling);
+        // Scans the parent, leaving the sibling queued
+        assert!(!gc.collect_step(1));
+        unsafe { (*(parent as *mut Node)).next = child };
+        gc.write_barrier(parent, child);
+        while !gc.collect_step(1) {}
+        assert_eq!(gc.stats().1, 3);
+
+        // Store a young object into a promoted one between minor collections
+        let mut gc = GarbageCollector::with_generational(1024 * 1024);
+        let parent = new_node(&mut gc);
+        gc.add_root(parent);
+        gc.collect();
+        let child = new_node(&mut gc);
+        unsafe { (*(parent as *mut Node)).next = child };
+        gc.write_barrier(parent, child);
+        gc.collect();
+        assert_eq!(gc.stats().1, 2);
+    }
+
+    #[test]
+    fn test_gc_sweep_frees_exact_sizes() {
+        let mut gc = GarbageCollector::with_threshold(usize::MAX);
+        let mut kept = 0;
+        for size in 0..200 {
+            let object = gc.allocate(size).unwrap();
+            if size % 10 == 0 {
+                gc.add_root(object.data_ptr());
+                kept += gc.objects[&object.data_ptr()].size();
+            }
+        }
+
+        gc.collect();
+        assert_eq!(gc.stats().0, kept);
+        assert_eq!(gc.stats().1, 20);
+
+        gc.roots.borrow_mut().clear();
+        gc.collect();
+        assert_eq!(gc.stats(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_gc_external_mark_keeps_object() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let pinned = gc.allocate(16).unwrap();
+        let child = gc.allocate(16).unwrap().data_ptr();
+        gc.add_reference(pinned.data_ptr(), child);
+        gc.allocate(16).unwrap();
+
+        pinned.mark();
+        gc.collect();
+        assert_eq!(gc.stats().1, 2);
+        assert!(!pinned.is_marked());
+
+        // The mark only pins the object for one collection
+        gc.collect();
+        assert_eq!(gc.stats().1, 0);
+    }
+
+    #[test]
+    fn test_gc_collect_freed() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let kept = gc.allocate(16).unwrap().data_ptr();
+        let garbage = gc.allocate(16).unwrap().data_ptr();
+        gc.add_root(kept);
+
+        assert_eq!(gc.collect_freed(), vec![garbage]);
+        assert_eq!(gc.collect_freed(), vec![]);
+    }
+
+    #[test]
+    fn test_gc_events() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&events);
+        gc.on_event(Box::new(move |event| sink.borrow_mut().push(event)));
+
+        let kept = gc.allocate(16).unwrap().data_ptr();
+        let garbage = gc.allocate(16).unwrap().data_ptr();
+        let garbage_bytes = gc.objects[&garbage].size();
+        gc.allocate(16).unwrap();
+        gc.add_root(kept);
+        gc.collect();
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], GcEvent::CollectionStarted);
+        match events[1] {
+            GcEvent::CollectionFinished {
+                freed_bytes,
+                freed_objects,
+                ..
+            } => {
+                assert_eq!(freed_objects, 2);
+                assert_eq!(freed_bytes, 2 * garbage_bytes);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_gc_threshold_event() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let mut gc = GarbageCollector::with_thresholds(1024 * 1024, 1);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&events);
+        gc.on_event(Box::new(move |event| sink.borrow_mut().push(event)));
+
+        gc.allocate(16).unwrap();
+        assert!(events.borrow().is_empty());
+        gc.allocate(16).unwrap();
+        assert_eq!(
+            events.borrow()[..2],
+            [GcEvent::ThresholdExceeded, GcEvent::CollectionStarted]
+        );
+    }
+
+    #[test]
+    fn test_gc_threshold_adapts_to_live_bytes() {
+        let mut gc = GarbageCollector::with_threshold(1024);
+        gc.set_growth_factor(3.0).unwrap();
+        assert!(gc.set_growth_factor(0.5).is_err());
+        assert!(gc.set_growth_factor(f64::NAN).is_err());
+
+        let mut live = Vec::new();
+        for _ in 0..20 {
+            let ptr = gc.allocate(100).unwrap().data_ptr();
+            gc.add_root(ptr);
+            live.push(ptr);
+        }
+        gc.collect();
+        let live_bytes = gc.stats().0;
+        assert!(live_bytes > 1024);
+        assert_eq!(gc.thresholds().0, live_bytes * 3);
+
+        // Once the live set is gone the threshold falls back to the initial one
+        for &ptr in &live {
+            gc.remove_root(ptr);
+        }
+        gc.collect();
+        assert_eq!(gc.thresholds().0, 1024);
+    }
+
+    #[test]
+    fn test_gc_generational_threshold_ignores_old_bytes() {
+        let mut gc = GarbageCollector::with_generational(1024);
+        for _ in 0..20 {
+            let ptr = gc.allocate(100).unwrap().data_ptr();
+            gc.add_root(ptr);
+        }
+        gc.collect();
+        assert!(gc.stats().0 > 1024);
+        assert_eq!(gc.thresholds().0, 1024);
+
+        // Minor collections still run once the young generation fills up
+        let collections = gc.collection_count();
+        for _ in 0..20 {
+            gc.allocate(100).unwrap();
+        }
+        assert!(gc.collection_count() >= collections + 2);
+    }
+
+    #[test]
+    fn test_gc_scan_region() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let on_stack = gc.allocate(16).unwrap().data_ptr();
+        let child = gc.allocate(16).unwrap().data_ptr();
+        gc.add_reference(on_stack, child);
+        gc.allocate(16).unwrap();
+
+        // An operand stack holding the pointer among plain integers
+        let mut stack = [0usize; 8];
+        stack[0] = 42;
+        stack[5] = on_stack as usize;
+        unsafe { gc.scan_region(stack.as_ptr() as *const u8, size_of_val(&stack)) };
+        gc.collect();
+        assert_eq!(gc.stats().1, 2);
+
+        // Without a fresh scan the objects are no longer found
+        gc.collect();
+        assert_eq!(gc.stats().1, 0);
+    }
+
+    #[test]
+    fn test_gc_root_guard() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let ptr = gc.allocate(16).unwrap().data_ptr();
+        let root = gc.root(ptr);
+        assert_eq!(root.ptr(), ptr);
+
+        gc.collect();
+        assert_eq!(gc.stats().1, 1);
+
+        drop(root);
+        gc.collect();
+        assert_eq!(gc.stats().1, 0);
+    }
+
+    #[test]
+    fn test_gc_collect_within() {
+        let mut gc = GarbageCollector::with_threshold(usize::MAX);
+        let chain: Vec<_> = (0..10_000)
+            .map(|_| gc.allocate(8).unwrap().data_ptr())
+            .collect();
+        for pair in chain.windows(2) {
+            gc.add_reference(pair[0], pair[1]);
+        }
+        gc.add_root(chain[0]);
+        for _ in 0..100 {
+            gc.allocate(8).unwrap();
+        }
+
+        assert_eq!(
+            gc.collect_within(Duration::ZERO),
+            CollectOutcome::Interrupted
+        );
+        assert_eq!(gc.stats().1, 10_100);
+        assert_eq!(gc.collection_count(), 0);
+
+        assert_eq!(
+            gc.collect_within(Duration::from_secs(60)),
+            CollectOutcome::Completed
+        );
+        assert_eq!(gc.stats().1, 10_000);
+        assert_eq!(gc.collection_count(), 1);
+    }
+
+    #[test]
+    fn test_gc_dump_dot() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let a = gc.allocate(16).unwrap().data_ptr();
+        let b = gc.allocate(32).unwrap().data_ptr();
+        let c = gc.allocate(8).unwrap().data_ptr();
+        gc.add_root(a);
+        gc.add_reference(a, b);
+        gc.add_reference(a, c);
+        gc.add_reference(b, c);
+
+        let dot = gc.dump_dot();
+        assert!(dot.starts_with("digraph heap {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert_eq!(dot.matches("[label=").count(), 3);
+        assert_eq!(dot.matches(" -> ").count(), 3);
+        assert_eq!(dot.matches("style=bold").count(), 1);
+        assert!(dot.contains(&format!("\"{:p}\" -> \"{:p}\";", b, c)));
+        assert!(dot.contains("32 bytes"));
+    }
+
+    #[test]
+    fn test_gc_mark_bits_match_reachability() {
+        let mut gc = GarbageCollector::with_threshold(usize::MAX);
+        let objects: Vec<_> = (0..5000)
+            .map(|_| gc.allocate(8).unwrap().data_ptr())
+            .collect();
+
+        // Deterministic pseudo-random graph with a handful of roots
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % bound
+        };
+        for _ in 0..4000 {
+            gc.add_reference(objects[next(5000)], objects[next(5000)]);
+        }
+        let roots: Vec<_> = (0..10).map(|_| objects[next(5000)]).collect();
+        for &root in &roots {
+            gc.add_root(root);
+        }
+
+        // Reachability computed independently with a visited set
+        let mut reachable = HashSet::new();
+        let mut pending = roots.clone();
+        while let Some(ptr) = pending.pop() {
+            if reachable.insert(ptr) {
+                if let Some(children) = gc.references.get(&ptr) {
+                    pending.extend(children.iter().copied());
+                }
+            }
+        }
+
+        let freed: HashSet<_> = gc.collect_freed().into_iter().collect();
+        let survivors: HashSet<_> = gc.objects.keys().copied().collect();
+        assert_eq!(survivors, reachable);
+        assert_eq!(freed.len() + survivors.len(), 5000);
+        assert_eq!(gc.traced_objects, reachable.len());
+    }
+
+    #[test]
+    fn test_gc_allocate_retries_after_collecting() {
+        let mut gc = GarbageCollector::with_threshold(64);
+        gc.allocate(16).unwrap();
+
+        // The first attempt fails, so the collector frees garbage and retries
+        gc.failing_allocs = 1;
+        let object = gc.allocate(24).unwrap();
+        assert_eq!(gc.collection_count(), 1);
+        assert_eq!(object.size(), 24);
+        assert!(!object.is_marked());
+        assert_eq!(gc.stats().1, 1);
+
+        // Both attempts fail
+        gc.failing_allocs = 2;
+        assert!(gc.allocate(24).is_none());
+        assert_eq!(gc.stats().1, 0);
+    }
+
+    #[test]
+    fn test_gc_object_count_threshold() {
+        // Byte threshold is never reached by these allocations
+        let mut gc = GarbageCollector::with_thresholds(1024 * 1024, 8);
+        assert_eq!(gc.thresholds(), (1024 * 1024, 8));
+
+        for _ in 0..8 {
+            gc.allocate(1).unwrap();
+        }
+        assert_eq!(gc.collection_count(), 0);
+
+        // The ninth allocation sees 8 tracked objects and collects first
+        gc.allocate(1).unwrap();
+        assert_eq!(gc.collection_count(), 1);
+        let (allocated, total, _) = gc.stats();
+        assert!(allocated < 1024 * 1024);
+        assert_eq!(total, 1);
+    }
+
+    #[test]
+    fn test_gc_object_threshold_adapts_to_live_objects() {
+        let mut gc = GarbageCollector::with_thresholds(1024 * 1024, 4);
+        for _ in 0..40 {
+            let ptr = gc.allocate(1).unwrap().data_ptr();
+            gc.add_root(ptr);
+        }
+        // The threshold doubles with the live set instead of collecting on
+        // every allocation once 4 objects are live
+        assert_eq!(gc.collection_count(), 4);
+        assert_eq!(gc.thresholds().1, 64);
+
+        // Generational mode counts only young objects against the threshold
+        let mut gc = GarbageCollector {
+            generations: Some(Generations::default()),
+            ..GarbageCollector::with_thresholds(1024 * 1024, 4)
+        };
+        for _ in 0..40 {
+            let ptr = gc.allocate(1).unwrap().data_ptr();
+            gc.add_root(ptr);
+        }
+        // Promoted objects do not raise the young threshold, so a minor
+        // collection runs as soon as 4 young objects are live again
+        gc.collect();
+        assert_eq!(gc.thresholds().1, 4);
+        let collections = gc.collection_count();
+        for _ in 0..4 {
+            gc.allocate(1).unwrap();
+        }
+        assert_eq!(gc.collection_count(), collections);
+        for _ in 0..5 {
+            gc.allocate(1).unwrap();
+        }
+        assert_eq!(gc.collection_count(), collections + 2);
+    }
+
+    #[test]
+    fn test_gc_mark_during_incremental_cycle() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let chain: Vec<_> = (0..6)
+            .map(|_| gc.allocate(16).unwrap().data_ptr())
+            .collect();
+        for pair in chain.windows(2) {
+            gc.add_reference(pair[0], pair[1]);
+        }
+        gc.add_root(chain[0]);
+        let parent = gc.allocate(16).unwrap();
+        let child = gc.allocate(16).unwrap().data_ptr();
+        gc.add_reference(parent.data_ptr(), child);
+
+        // Pinned after the cycle started: the child is only reachable through it
+        assert!(!gc.collect_step(2));
+        parent.mark();
+        while !gc.collect_step(2) {}
+        assert_eq!(gc.stats().1, 8);
+        assert!(gc.objects.contains_key(&child));
+
+        // The pin only lasted for that cycle
+        while !gc.collect_step(2) {}
+        assert_eq!(gc.stats().1, 6);
+    }
+
+    #[test]
+    fn test_gc_collect_abandons_incremental_cycle() {
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let chain: Vec<_> = (0..10)
+            .map(|_| gc.allocate(16).unwrap().data_ptr())
+            .collect();
+        for pair in chain.windows(2) {
+            gc.add_reference(pair[0], pair[1]);
+        }
+        gc.add_root(chain[0]);
+        assert!(!gc.collect_step(3));
+
+        // Objects marked by the unfinished cycle are not kept as pinned
+        gc.remove_root(chain[0]);
+        gc.collect();
+        assert_eq!(gc.stats().1, 0);
+        assert!(gc.gray.is_none());
+    }
+
+    #[test]
+    fn test_gc_ref_resolves_after_move() {
+        let finalized = Rc::new(RefCell::new(Vec::new()));
+        for generational in [false, true] {
+            let mut gc = if generational {
+                GarbageCollector::with_generational(1024 * 1024)
+            } else {
+                GarbageCollector::with_threshold(1024 * 1024)
+            };
+            let handle = gc.allocate_ref(32).unwrap();
+            let original = handle.get(&gc).unwrap();
+            let child = gc.allocate(16).unwrap().data_ptr();
+            let parent = gc.allocate(16).unwrap().data_ptr();
+            gc.add_reference(original, child);
+            gc.add_reference(parent, original);
+            gc.add_root(original);
+            gc.add_root(parent);
+            let sink = Rc::clone(&finalized);
+            gc.finalizers
+                .insert(original, Box::new(move |ptr| sink.borrow_mut().push(ptr)));
+            unsafe { original.write_bytes(7, 32) };
+
+            let moved = unsafe { gc.relocate(original) }.unwrap();
+            assert_ne!(moved, original);
+            assert_eq!(handle.get(&gc), Some(moved));
+            assert_eq!(unsafe { *moved.add(31) }, 7);
+            assert_eq!(unsafe { gc.relocate(original) }, None);
+
+            // The root, references and finalizer all follow the object
+            gc.collect();
+            assert_eq!(gc.stats().1, 3);
+            assert!(gc.objects.contains_key(&child));
+            assert_eq!(handle.get(&gc), Some(moved));
+
+            // Still reachable through the parent's rekeyed reference
+            gc.remove_root(moved);
+            gc.collect_major();
+            assert_eq!(gc.stats().1, 3);
+            gc.remove_root(parent);
+            gc.collect_major();
+            assert_eq!(gc.stats().1, 0);
+            assert_eq!(handle.get(&gc), None);
+            assert_eq!(finalized.borrow().last(), Some(&moved));
+        }
+        assert_eq!(finalized.borrow().len(), 2);
+    }
 }
@@ -1,9 +1,38 @@
 // Pain runtime library
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod macros;
+
 pub mod allocator;
+#[cfg(feature = "std")]
+pub mod binary;
+#[cfg(feature = "cbor")]
+pub mod cbor;
 pub mod gc;
+#[cfg(feature = "std")]
 pub mod object;
+#[cfg(feature = "serde")]
+mod serde_impl;
 
-pub use allocator::{Arena, BumpAllocator};
-pub use gc::GarbageCollector;
-pub use object::{ClassInstance, Object, Runtime, Value};
+pub use allocator::{
+    AllocSource, AllocatorInfo, Arena, ArenaSnapshot, BumpAllocator, Checkpoint, ScopedAlloc,
+};
+#[cfg(feature = "alloc-stats")]
+pub use allocator::{AllocStats, SizeBucket};
+#[cfg(feature = "std")]
+pub use allocator::{BumpGlobalAlloc, SyncArena};
+#[cfg(feature = "std")]
+pub use binary::{DecodeError, EncodeError};
+pub use gc::{
+    CollectOutcome, Collector, GarbageCollector, GcEvent, GcRef, GcStrategy, Root, Traceable, Weak,
+};
+#[cfg(feature = "std")]
+pub use object::{
+    AllocateHook, ClassDef, ClassInstance, CollectHook, CompareOp, FunctionRef, Handle, IndexError,
+    InternId, ListMerge, MethodRef, NativeFn, NumericStats, Object, PathError, Runtime,
+    RuntimeError, RuntimeMetrics, TypeError, Value, ValueDiff, ValueError, ValueRef,
+};
@@ -0,0 +1,37 @@
+// Assertion macros for comparing runtime values in tests
+
+/// Assert that two values are deeply equal, panicking with every differing
+/// path (see `Value::deep_eq_report`) instead of two full `Debug` dumps
+#[macro_export]
+macro_rules! assert_value_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if let Err(diffs) = $crate::Value::deep_eq_report(left, right) {
+                    panic!(
+                        "assertion `left == right` failed: values differ\n{}",
+                        $crate::object::format_diffs(&diffs)
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Assert that two values are deeply equal, treating floats as equal when they
+/// differ by at most `epsilon` (see `Value::approx_eq_report`)
+#[macro_export]
+macro_rules! assert_value_approx_eq {
+    ($left:expr, $right:expr, $epsilon:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if let Err(diffs) = $crate::Value::approx_eq_report(left, right, $epsilon) {
+                    panic!(
+                        "assertion `left ≈ right` failed: values differ\n{}",
+                        $crate::object::format_diffs(&diffs)
+                    );
+                }
+            }
+        }
+    };
+}
@@ -1,194 +1,3694 @@
 // Object model for Pain runtime
 
-use crate::allocator::Arena;
+use crate::allocator::{Arena, ArenaSnapshot};
+use crate::gc::{Collector, GarbageCollector, GcRef, GcStrategy, Traceable};
+use std::alloc::Layout;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ptr::NonNull;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::time::Duration;
+use thiserror::Error;
 
 /// Pain runtime value types
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Values are totally ordered so they can be sorted and used as ordered map
+/// keys. Different types order as `None < Bool < numbers < String < Bytes < List <
+/// Array < Tuple < Dict < Object < Ref`. Ints and floats compare numerically with each other,
+/// an int sorting just before an equal float since the two are never equal.
+/// NaN equals itself and sorts after every other float
+#[derive(Debug, Clone)]
 pub enum Value {
     Int(i64),
     Float(f64),
     Bool(bool),
     String(String),
     None,
-    Object(ClassInstance), // Class instance
-    List(Vec<Value>),      // Dynamic list
-    Array(Vec<Value>),     // Fixed-size array (for now, same as list)
+    Object(ClassInstance),     // Class instance
+    List(Vec<Value>),          // Dynamic list
+    Array(Vec<Value>),         // Fixed-size array
+    Tuple(Box<[Value]>),       // Immutable fixed-arity tuple
+    Dict(Vec<(Value, Value)>), // Insertion-ordered map with unique keys
+    Bytes(Vec<u8>),            // Raw byte buffer
+    Ref(GcRef),                // Handle to a GC-managed object
 }
 
-/// Class instance - stores field values
-#[derive(Debug, Clone, PartialEq)]
-pub struct ClassInstance {
-    pub class_name: String,
-    pub fields: HashMap<String, Value>,
+/// Error returned when a value operation gets an operand of the wrong type
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("type error: {0}")]
+pub struct TypeError(pub String);
+
+/// Error returned when an index or range falls outside a list or array
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum IndexError {
+    #[error("range {start}..{end} out of bounds for length {len}")]
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    #[error("index {index} out of bounds for length {len}")]
+    OutOfBounds { index: usize, len: usize },
+    #[error("value is not a list or array")]
+    NotASequence,
+    #[error("value is not an array")]
+    NotAnArray,
+    #[error("cannot change the length of a fixed-size array")]
+    FixedSize,
 }
 
-impl ClassInstance {
-    pub fn new(class_name: String) -> Self {
-        Self {
-            class_name,
-            fields: HashMap::new(),
+/// Error returned by dotted-path field access on nested objects
+/// `path` is the prefix of the requested path up to the failing segment
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PathError {
+    #[error("no field at `{path}`")]
+    MissingField { path: String },
+    #[error("`{path}` is not an object")]
+    NotAnObject { path: String },
+}
+
+/// Error returned by arithmetic on values and conversions out of them
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ValueError {
+    #[error("expected {expected}, got {got}")]
+    TypeMismatch { expected: &'static str, got: String },
+    #[error("unsupported operand types for {op}: {left} and {right}")]
+    UnsupportedOperands {
+        op: &'static str,
+        left: &'static str,
+        right: &'static str,
+    },
+    #[error("integer division by zero")]
+    DivisionByZero,
+    #[error("integer overflow in {0}")]
+    Overflow(&'static str),
+    #[error("value nested deeper than {0} levels")]
+    DepthExceeded(usize),
+    #[error(transparent)]
+    Index(#[from] IndexError),
+}
+
+/// Nesting limit threaded through recursive `Value` operations
+#[derive(Debug, Clone, Copy)]
+struct DepthLimit(usize);
+
+impl DepthLimit {
+    const UNBOUNDED: DepthLimit = DepthLimit(usize::MAX);
+
+    /// Check a value at nesting level `depth` and return its children's level
+    fn enter(self, depth: usize) -> Result<usize, ValueError> {
+        if depth > self.0 {
+            return Err(ValueError::DepthExceeded(self.0));
         }
+        Ok(depth + 1)
     }
+}
 
-    pub fn get_field(&self, name: &str) -> Option<&Value> {
-        self.fields.get(name)
-    }
+/// Summary statistics over a numeric list, computed in f64
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericStats {
+    pub count: usize,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
 
-    pub fn set_field(&mut self, name: String, value: Value) {
-        self.fields.insert(name, value);
+/// A single difference found by `Value::deep_eq_report`
+/// `left`/`right` are `None` when the element or field is missing on that side
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueDiff {
+    pub path: String,
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+}
+
+impl fmt::Display for ValueDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |value: &Option<Value>| match value {
+            Some(value) => format!("{:?}", value),
+            None => "<missing>".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} != {}",
+            self.path,
+            side(&self.left),
+            side(&self.right)
+        )
     }
 }
 
-/// Runtime object representation
-pub struct Object {
-    pub value: Value,
-    // Future: metadata, type info, etc.
+/// Format diffs one per line, as used by the value assertion macros
+#[doc(hidden)]
+pub fn format_diffs(diffs: &[ValueDiff]) -> String {
+    diffs
+        .iter()
+        .map(|diff| format!("  {}", diff))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-impl Object {
-    pub fn new(value: Value) -> Self {
-        Self { value }
+/// Comparison operators usable in `Value::compare` and `Value::compare_chain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// How `Value::deep_merge_with` combines two lists found at the same key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListMerge {
+    /// Overlay list replaces the base list
+    #[default]
+    Replace,
+    /// Overlay elements are appended to the base list
+    Concat,
+}
+
+impl Value {
+    /// Build nested lists of `fill` with the given shape, so `[2, 3]` produces a
+    /// list of 2 lists of 3 elements each. An empty shape returns `fill` itself
+    pub fn filled_nested(shape: &[usize], fill: Value) -> Value {
+        match shape.split_first() {
+            None => fill,
+            Some((&len, rest)) => Value::List(vec![Value::filled_nested(rest, fill); len]),
+        }
     }
 
-    pub fn as_int(&self) -> Option<i64> {
-        match &self.value {
-            Value::Int(n) => Some(*n),
+    /// Recursively merge `overlay` on top of `self`
+    /// Both values must be objects; lists found at the same key are replaced
+    pub fn deep_merge(&self, overlay: &Value) -> Result<Value, TypeError> {
+        self.deep_merge_with(overlay, ListMerge::Replace)
+    }
+
+    /// Recursively merge `overlay` on top of `self` with the given list strategy
+    /// Keys present in both objects are merged, keys present in only one are kept,
+    /// and on a type mismatch at a key the overlay value wins
+    pub fn deep_merge_with(&self, overlay: &Value, lists: ListMerge) -> Result<Value, TypeError> {
+        match (self, overlay) {
+            (Value::Object(_), Value::Object(_)) => Ok(merge_values(self, overlay, lists)),
+            _ => Err(TypeError(
+                "deep_merge requires two object values".to_string(),
+            )),
+        }
+    }
+
+    /// Compare two values with a language-level comparison operator
+    /// Ints and floats compare numerically, strings lexicographically and bools
+    /// with `false < true`. Equality works on any pair of values, while ordering
+    /// other combinations is a type error. NaN is unordered, so only `Ne` holds
+    pub fn compare(&self, op: CompareOp, other: &Value) -> Result<bool, TypeError> {
+        let ordering = match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => match op {
+                CompareOp::Eq => return Ok(self == other),
+                CompareOp::Ne => return Ok(self != other),
+                _ => {
+                    return Err(TypeError(format!(
+                        "cannot order {:?} against {:?}",
+                        self, other
+                    )))
+                }
+            },
+        };
+
+        Ok(match op {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        })
+    }
+
+    /// Evaluate a comparison chain such as `a < b <= c`, where `ops[i]` compares
+    /// `operands[i]` with `operands[i + 1]`. Evaluation stops at the first false
+    /// comparison, so later operands are never compared
+    pub fn compare_chain(operands: &[Value], ops: &[CompareOp]) -> Result<Value, TypeError> {
+        if operands.len() != ops.len() + 1 {
+            return Err(TypeError(format!(
+                "comparison chain has {} operands for {} operators",
+                operands.len(),
+                ops.len()
+            )));
+        }
+
+        for (pair, &op) in operands.windows(2).zip(ops) {
+            if !pair[0].compare(op, &pair[1])? {
+                return Ok(Value::Bool(false));
+            }
+        }
+        Ok(Value::Bool(true))
+    }
+
+    /// Deeply compare two values, returning every differing leaf with its path
+    /// (e.g. `$[1].x`) instead of stopping at the first difference.
+    /// Floats use IEEE semantics, so NaN never equals NaN
+    pub fn deep_eq_report(&self, other: &Value) -> Result<(), Vec<ValueDiff>> {
+        let mut diffs = Vec::new();
+        collect_diffs("$".to_string(), self, other, None, &mut diffs);
+        if diffs.is_empty() {
+            Ok(())
+        } else {
+            Err(diffs)
+        }
+    }
+
+    /// Like `deep_eq_report`, but floats at the same path are equal when they
+    /// differ by at most `epsilon`
+    pub fn approx_eq_report(&self, other: &Value, epsilon: f64) -> Result<(), Vec<ValueDiff>> {
+        let mut diffs = Vec::new();
+        collect_diffs("$".to_string(), self, other, Some(epsilon), &mut diffs);
+        if diffs.is_empty() {
+            Ok(())
+        } else {
+            Err(diffs)
+        }
+    }
+
+    /// Compute count, sum, min, max and mean over a non-empty list or array of
+    /// ints and floats. Ints are promoted to f64 and the sum is accumulated in
+    /// f64, so large int sums lose precision instead of overflowing
+    pub fn numeric_stats(&self) -> Result<NumericStats, TypeError> {
+        let items = match self {
+            Value::List(items) | Value::Array(items) => items,
+            _ => {
+                return Err(TypeError(
+                    "numeric_stats requires a list or array".to_string(),
+                ))
+            }
+        };
+        if items.is_empty() {
+            return Err(TypeError(
+                "numeric_stats requires a non-empty list".to_string(),
+            ));
+        }
+
+        let mut sum = 0.0;
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        for (index, item) in items.iter().enumerate() {
+            let n = match item {
+                Value::Int(n) => *n as f64,
+                Value::Float(f) => *f,
+                _ => {
+                    return Err(TypeError(format!(
+                        "numeric_stats: element {} is not a number",
+                        index
+                    )))
+                }
+            };
+            sum += n;
+            min = min.min(n);
+            max = max.max(n);
+        }
+
+        Ok(NumericStats {
+            count: items.len(),
+            sum,
+            min,
+            max,
+            mean: sum / items.len() as f64,
+        })
+    }
+
+    /// Name of the value's type for diagnostics, such as `"int"` or `"list"`
+    /// Objects report their class name
+    pub fn type_name(&self) -> Cow<'_, str> {
+        match self {
+            Value::Object(instance) => Cow::Borrowed(&instance.class_name),
+            _ => Cow::Borrowed(kind_name(self)),
+        }
+    }
+
+    /// Truthiness used by conditional branches
+    /// Zero numbers, empty strings, sequences and dicts, `None` and `false` are falsy.
+    /// NaN is nonzero and therefore truthy, and objects and refs are always truthy
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Int(n) => *n != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Bytes(bytes) => !bytes.is_empty(),
+            Value::List(items) | Value::Array(items) => !items.is_empty(),
+            Value::Tuple(items) => !items.is_empty(),
+            Value::Dict(entries) => !entries.is_empty(),
+            Value::None => false,
+            Value::Object(_) | Value::Ref(_) => true,
+        }
+    }
+
+    /// Number of elements of a list, array or tuple, entries of a dict or
+    /// bytes of a byte buffer
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes.len()),
+            Value::List(items) | Value::Array(items) => Some(items.len()),
+            Value::Tuple(items) => Some(items.len()),
+            Value::Dict(entries) => Some(entries.len()),
             _ => None,
         }
     }
 
-    pub fn as_float(&self) -> Option<f64> {
-        match &self.value {
-            Value::Float(f) => Some(*f),
-            Value::Int(n) => Some(*n as f64),
+    /// Check whether a collection has no elements
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
             _ => None,
         }
     }
 
-    pub fn as_bool(&self) -> Option<bool> {
-        match &self.value {
-            Value::Bool(b) => Some(*b),
+    /// Get the byte at `index` of a byte buffer
+    pub fn byte_at(&self, index: usize) -> Option<u8> {
+        self.as_bytes()?.get(index).copied()
+    }
+
+    /// Look up `key` in a dict, returning `None` for a missing key or a
+    /// non-dict value. Lookup is a linear scan in insertion order
+    pub fn dict_get(&self, key: &Value) -> Option<&Value> {
+        match self {
+            Value::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
             _ => None,
         }
     }
 
-    pub fn as_string(&self) -> Option<&String> {
-        match &self.value {
-            Value::String(s) => Some(s),
+    /// Insert into a dict, returning the previous value for `key`
+    /// An existing key keeps its position and has its value replaced
+    pub fn dict_insert(&mut self, key: Value, value: Value) -> Result<Option<Value>, TypeError> {
+        let entries = match self {
+            Value::Dict(entries) => entries,
+            _ => return Err(TypeError("dict_insert requires a dict".to_string())),
+        };
+        match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => Ok(Some(mem::replace(existing, value))),
+            None => {
+                entries.push((key, value));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Append to a list
+    pub fn push(&mut self, item: Value) -> Result<(), ValueError> {
+        self.list_mut()?.push(item);
+        Ok(())
+    }
+
+    /// Remove the last element of a list, or `None` if it is empty
+    pub fn pop(&mut self) -> Result<Option<Value>, ValueError> {
+        Ok(self.list_mut()?.pop())
+    }
+
+    /// Get the element at `index` of a list, array or tuple
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        match self {
+            Value::List(items) | Value::Array(items) => items.get(index),
+            Value::Tuple(items) => items.get(index),
             _ => None,
         }
     }
 
-    pub fn as_list(&self) -> Option<&Vec<Value>> {
-        match &self.value {
-            Value::List(v) | Value::Array(v) => Some(v),
+    /// Replace the element at `index` of a list
+    /// Arrays are updated through `array_set` and tuples are immutable
+    pub fn set(&mut self, index: usize, value: Value) -> Result<(), ValueError> {
+        let items = self.list_mut()?;
+        let len = items.len();
+        let slot = items
+            .get_mut(index)
+            .ok_or(IndexError::OutOfBounds { index, len })?;
+        *slot = value;
+        Ok(())
+    }
+
+    fn list_mut(&mut self) -> Result<&mut Vec<Value>, ValueError> {
+        match self {
+            Value::List(items) => Ok(items),
+            _ => Err(ValueError::TypeMismatch {
+                expected: "list",
+                got: self.type_name().into_owned(),
+            }),
+        }
+    }
+
+    /// Recursively copy the value so the copy shares no owned data with it
+    ///
+    /// Owned values always form a tree, so the copy cannot loop. The only shared
+    /// identities are `Ref`s into the GC heap, which are copied as references
+    /// and keep pointing at the same object rather than being followed
+    pub fn deep_clone(&self) -> Value {
+        self.clone_within(DepthLimit::UNBOUNDED, 0)
+            .expect("unbounded depth")
+    }
+
+    fn clone_within(&self, limit: DepthLimit, depth: usize) -> Result<Value, ValueError> {
+        let depth = limit.enter(depth)?;
+        let clone_items = |items: &[Value]| {
+            items
+                .iter()
+                .map(|item| item.clone_within(limit, depth))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            Value::List(items) => Value::List(clone_items(items)?),
+            Value::Array(items) => Value::Array(clone_items(items)?),
+            Value::Tuple(items) => Value::Tuple(clone_items(items)?.into()),
+            Value::Dict(entries) => Value::Dict(
+                entries
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            key.clone_within(limit, depth)?,
+                            value.clone_within(limit, depth)?,
+                        ))
+                    })
+                    .collect::<Result<_, ValueError>>()?,
+            ),
+            Value::Object(instance) => {
+                let fields = instance
+                    .fields
+                    .iter()
+                    .map(|(name, value)| Ok((name.clone(), value.clone_within(limit, depth)?)))
+                    .collect::<Result<Vec<_>, ValueError>>()?;
+                Value::Object(ClassInstance::from_fields(
+                    instance.class_name.clone(),
+                    fields,
+                ))
+            }
+            scalar => scalar.clone(),
+        })
+    }
+
+    /// Check that two values have identical structure, which is stricter than
+    /// `==`: floats must match bit for bit (so `0.0` differs from `-0.0`), dict
+    /// entries must be in the same order and refs must point at the same
+    /// object. A `deep_clone` is always structurally equal to its source
+    pub fn structural_eq(&self, other: &Value) -> bool {
+        self.structural_eq_within(other, DepthLimit::UNBOUNDED, 0)
+            .expect("unbounded depth")
+    }
+
+    fn structural_eq_within(
+        &self,
+        other: &Value,
+        limit: DepthLimit,
+        depth: usize,
+    ) -> Result<bool, ValueError> {
+        let depth = limit.enter(depth)?;
+        let pairs_eq = |pairs: &mut dyn Iterator<Item = (&Value, &Value)>| {
+            for (x, y) in pairs {
+                if !x.structural_eq_within(y, limit, depth)? {
+                    return Ok::<bool, ValueError>(false);
+                }
+            }
+            Ok(true)
+        };
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => Ok(a.to_bits() == b.to_bits()),
+            (Value::List(a), Value::List(b)) | (Value::Array(a), Value::Array(b)) => {
+                Ok(a.len() == b.len() && pairs_eq(&mut a.iter().zip(b))?)
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                Ok(a.len() == b.len() && pairs_eq(&mut a.iter().zip(b.iter()))?)
+            }
+            (Value::Dict(a), Value::Dict(b)) => Ok(a.len() == b.len()
+                && pairs_eq(
+                    &mut a
+                        .iter()
+                        .zip(b)
+                        .flat_map(|((ka, va), (kb, vb))| [(ka, kb), (va, vb)]),
+                )?),
+            (Value::Object(a), Value::Object(b)) => {
+                if a.class_name != b.class_name || a.fields.len() != b.fields.len() {
+                    return Ok(false);
+                }
+                for (name, value) in &a.fields {
+                    match b.fields.get(name) {
+                        Some(other) if value.structural_eq_within(other, limit, depth)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(self == other),
+        }
+    }
+
+    /// Estimate the bytes this value occupies: its own `Value` slot plus every
+    /// heap buffer it owns, counted by capacity. Map overhead is approximated
+    /// as one entry plus a control byte per bucket. Objects behind a `Ref`
+    /// belong to the GC and are not counted
+    pub fn heap_size(&self) -> usize {
+        self.heap_size_within(DepthLimit::UNBOUNDED)
+            .expect("unbounded depth")
+    }
+
+    fn heap_size_within(&self, limit: DepthLimit) -> Result<usize, ValueError> {
+        Ok(mem::size_of::<Value>() + self.owned_bytes(limit, 0)?)
+    }
+
+    fn owned_bytes(&self, limit: DepthLimit, depth: usize) -> Result<usize, ValueError> {
+        let depth = limit.enter(depth)?;
+        let children = |items: &mut dyn Iterator<Item = &Value>| {
+            let mut sum = 0;
+            for item in items {
+                sum += item.owned_bytes(limit, depth)?;
+            }
+            Ok::<usize, ValueError>(sum)
+        };
+        Ok(match self {
+            Value::String(s) => s.capacity(),
+            Value::Bytes(bytes) => bytes.capacity(),
+            Value::List(items) | Value::Array(items) => {
+                items.capacity() * mem::size_of::<Value>() + children(&mut items.iter())?
+            }
+            Value::Tuple(items) => {
+                items.len() * mem::size_of::<Value>() + children(&mut items.iter())?
+            }
+            Value::Dict(entries) => {
+                entries.capacity() * mem::size_of::<(Value, Value)>()
+                    + children(&mut entries.iter().flat_map(|(key, value)| [key, value]))?
+            }
+            Value::Object(instance) => {
+                let bucket = mem::size_of::<(String, Value)>() + 1;
+                let names: usize = instance.fields.keys().map(String::capacity).sum();
+                instance.class_name.capacity()
+                    + instance.fields.capacity() * bucket
+                    + names
+                    + children(&mut instance.fields.values())?
+            }
+            Value::Int(_) | Value::Float(_) | Value::Bool(_) | Value::None | Value::Ref(_) => 0,
+        })
+    }
+
+    /// Iterate the elements of a list, array or tuple, or the keys of a dict
+    /// in insertion order. Scalars, strings, bytes and objects return `None`
+    pub fn iter(&self) -> Option<impl Iterator<Item = &Value>> {
+        match self {
+            Value::List(items) | Value::Array(items) => Some(ValueIter::Items(items.iter())),
+            Value::Tuple(items) => Some(ValueIter::Items(items.iter())),
+            Value::Dict(entries) => Some(ValueIter::Keys(entries.iter())),
+            _ => None,
+        }
+    }
+
+    /// Consuming version of `iter`
+    pub fn into_items(self) -> Option<std::vec::IntoIter<Value>> {
+        match self {
+            Value::List(items) | Value::Array(items) => Some(items.into_iter()),
+            Value::Tuple(items) => Some(items.into_vec().into_iter()),
+            Value::Dict(entries) => {
+                let keys: Vec<Value> = entries.into_iter().map(|(key, _)| key).collect();
+                Some(keys.into_iter())
+            }
             _ => None,
         }
     }
+
+    /// Create a fixed-size array of `len` copies of `fill`
+    pub fn array(len: usize, fill: Value) -> Value {
+        Value::Array(vec![fill; len])
+    }
+
+    /// Get the element at `index` of an array
+    pub fn array_get(&self, index: usize) -> Result<&Value, IndexError> {
+        match self {
+            Value::Array(items) => items.get(index).ok_or(IndexError::OutOfBounds {
+                index,
+                len: items.len(),
+            }),
+            _ => Err(IndexError::NotAnArray),
+        }
+    }
+
+    /// Replace the element at `index` of an array, returning the old element
+    /// Arrays never change length, so there is no way to push or pop
+    pub fn array_set(&mut self, index: usize, value: Value) -> Result<Value, IndexError> {
+        match self {
+            Value::Array(items) => {
+                let len = items.len();
+                let slot = items
+                    .get_mut(index)
+                    .ok_or(IndexError::OutOfBounds { index, len })?;
+                Ok(mem::replace(slot, value))
+            }
+            _ => Err(IndexError::NotAnArray),
+        }
+    }
+
+    /// Replace the `[start, end)` range of a list or array with the elements of
+    /// `replacement`, returning the removed elements as a new list
+    /// Splicing an array must keep its length unchanged
+    pub fn splice(
+        &mut self,
+        start: usize,
+        end: usize,
+        replacement: Value,
+    ) -> Result<Value, IndexError> {
+        let fixed_size = matches!(self, Value::Array(_));
+        let items = match self {
+            Value::List(items) | Value::Array(items) => items,
+            _ => return Err(IndexError::NotASequence),
+        };
+        let replacement = match replacement {
+            Value::List(new_items) | Value::Array(new_items) => new_items,
+            _ => return Err(IndexError::NotASequence),
+        };
+        if start > end || end > items.len() {
+            return Err(IndexError::RangeOutOfBounds {
+                start,
+                end,
+                len: items.len(),
+            });
+        }
+        if fixed_size && replacement.len() != end - start {
+            return Err(IndexError::FixedSize);
+        }
+
+        let removed = items.splice(start..end, replacement).collect();
+        Ok(Value::List(removed))
+    }
 }
 
-/// Runtime context for managing objects and memory
-pub struct Runtime {
-    arena: Arena,
-    gc: crate::gc::GarbageCollector,
+enum ValueIter<'a> {
+    Items(std::slice::Iter<'a, Value>),
+    Keys(std::slice::Iter<'a, (Value, Value)>),
 }
 
-impl Runtime {
-    /// Create a new runtime instance
-    pub fn new() -> Result<Self, &'static str> {
-        Ok(Self {
-            arena: Arena::new(1024 * 1024)?, // 1MB default
-            gc: crate::gc::GarbageCollector::new(),
-        })
+impl<'a> Iterator for ValueIter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<&'a Value> {
+        match self {
+            ValueIter::Items(items) => items.next(),
+            ValueIter::Keys(entries) => entries.next().map(|(key, _)| key),
+        }
     }
+}
 
-    /// Create a new runtime with custom arena size
-    pub fn with_arena_size(size: usize) -> Result<Self, &'static str> {
-        Ok(Self {
-            arena: Arena::new(size)?,
-            gc: crate::gc::GarbageCollector::new(),
-        })
+#[derive(Clone, Copy)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl ArithOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
+            ArithOp::Rem => "%",
+        }
     }
+}
 
-    /// Create a new runtime with GC enabled and custom threshold
-    pub fn with_gc_threshold(threshold: usize) -> Result<Self, &'static str> {
-        Ok(Self {
-            arena: Arena::new(1024 * 1024)?,
-            gc: crate::gc::GarbageCollector::with_threshold(threshold),
-        })
+fn kind_name(value: &Value) -> &'static str {
+    match value {
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "str",
+        Value::Bytes(_) => "bytes",
+        Value::None => "none",
+        Value::Object(_) => "object",
+        Value::List(_) => "list",
+        Value::Array(_) => "array",
+        Value::Tuple(_) => "tuple",
+        Value::Dict(_) => "dict",
+        Value::Ref(_) => "ref",
     }
+}
 
-    /// Allocate memory in the runtime arena
-    pub fn allocate(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
-        self.arena.allocate(size, align)
+/// Apply an arithmetic operator with numeric promotion: two ints stay an int,
+/// any float operand promotes both to float, and `+` concatenates strings and
+/// byte buffers
+fn arith(op: ArithOp, left: &Value, right: &Value) -> Result<Value, ValueError> {
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) => {
+            if matches!(op, ArithOp::Div | ArithOp::Rem) && *b == 0 {
+                return Err(ValueError::DivisionByZero);
+            }
+            let result = match op {
+                ArithOp::Add => a.checked_add(*b),
+                ArithOp::Sub => a.checked_sub(*b),
+                ArithOp::Mul => a.checked_mul(*b),
+                ArithOp::Div => a.checked_div(*b),
+                ArithOp::Rem => a.checked_rem(*b),
+            };
+            result
+                .map(Value::Int)
+                .ok_or(ValueError::Overflow(op.symbol()))
+        }
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            let as_float = |value: &Value| match value {
+                Value::Int(n) => *n as f64,
+                Value::Float(f) => *f,
+                _ => unreachable!(),
+            };
+            let (a, b) = (as_float(left), as_float(right));
+            Ok(Value::Float(match op {
+                ArithOp::Add => a + b,
+                ArithOp::Sub => a - b,
+                ArithOp::Mul => a * b,
+                ArithOp::Div => a / b,
+                ArithOp::Rem => a % b,
+            }))
+        }
+        (Value::String(a), Value::String(b)) if matches!(op, ArithOp::Add) => {
+            Ok(Value::String(format!("{}{}", a, b)))
+        }
+        (Value::Bytes(a), Value::Bytes(b)) if matches!(op, ArithOp::Add) => {
+            Ok(Value::Bytes([a.as_slice(), b].concat()))
+        }
+        _ => Err(ValueError::UnsupportedOperands {
+            op: op.symbol(),
+            left: kind_name(left),
+            right: kind_name(right),
+        }),
     }
+}
 
-    /// Reset the runtime arena (free all allocations)
-    pub fn reset(&mut self) {
-        self.arena.reset();
+macro_rules! impl_arith {
+    ($($trait:ident, $method:ident, $op:expr;)*) => {$(
+        impl std::ops::$trait<&Value> for &Value {
+            type Output = Result<Value, ValueError>;
+
+            fn $method(self, rhs: &Value) -> Self::Output {
+                arith($op, self, rhs)
+            }
+        }
+
+        impl std::ops::$trait for Value {
+            type Output = Result<Value, ValueError>;
+
+            fn $method(self, rhs: Value) -> Self::Output {
+                arith($op, &self, &rhs)
+            }
+        }
+    )*};
+}
+
+// Float division and remainder follow IEEE semantics, so dividing a float by
+// zero yields an infinity or NaN instead of an error
+impl_arith! {
+    Add, add, ArithOp::Add;
+    Sub, sub, ArithOp::Sub;
+    Mul, mul, ArithOp::Mul;
+    Div, div, ArithOp::Div;
+    Rem, rem, ArithOp::Rem;
+}
+
+macro_rules! impl_try_from {
+    ($($target:ty, $expected:literal, |$value:ident| $convert:expr;)*) => {$(
+        impl TryFrom<&Value> for $target {
+            type Error = ValueError;
+
+            fn try_from($value: &Value) -> Result<$target, ValueError> {
+                $convert.ok_or_else(|| ValueError::TypeMismatch {
+                    expected: $expected,
+                    got: $value.type_name().into_owned(),
+                })
+            }
+        }
+
+        impl TryFrom<Value> for $target {
+            type Error = ValueError;
+
+            fn try_from(value: Value) -> Result<$target, ValueError> {
+                <$target>::try_from(&value)
+            }
+        }
+    )*};
+}
+
+// Floats accept ints the same way `Object::as_float` does
+impl_try_from! {
+    i64, "int", |value| match value {
+        Value::Int(n) => Some(*n),
+        _ => None,
+    };
+    f64, "float", |value| match value {
+        Value::Float(f) => Some(*f),
+        Value::Int(n) => Some(*n as f64),
+        _ => None,
+    };
+    bool, "bool", |value| match value {
+        Value::Bool(b) => Some(*b),
+        _ => None,
+    };
+    String, "str", |value| match value {
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    };
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Int(n)
     }
+}
 
-    /// Get memory usage statistics
-    pub fn memory_stats(&self) -> (usize, usize) {
-        (self.arena.total_used(), self.arena.total_capacity())
+impl From<f64> for Value {
+    fn from(f: f64) -> Value {
+        Value::Float(f)
     }
+}
 
-    /// Run garbage collection
-    pub fn collect_garbage(&mut self) -> usize {
-        self.gc.force_collect()
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
     }
+}
 
-    /// Get GC statistics
-    pub fn gc_stats(&self) -> (usize, usize, usize) {
-        self.gc.stats()
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
     }
+}
 
-    /// Manually trigger GC collection
-    pub fn gc_collect(&mut self) {
-        self.gc.collect();
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
     }
 }
 
-impl Default for Runtime {
-    fn default() -> Self {
-        Self::new().expect("Failed to create default runtime")
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Value {
+        Value::List(items)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Collecting values builds a list
+impl FromIterator<Value> for Value {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Value {
+        Value::List(iter.into_iter().collect())
+    }
+}
 
-    #[test]
-    fn test_value() {
-        use std::f64::consts::PI;
+impl Value {
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::None => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Float(_) => 2,
+            Value::String(_) => 3,
+            Value::Bytes(_) => 4,
+            Value::List(_) => 5,
+            Value::Array(_) => 6,
+            Value::Tuple(_) => 7,
+            Value::Dict(_) => 8,
+            Value::Object(_) => 9,
+            Value::Ref(_) => 10,
+        }
+    }
+}
 
-        let v1 = Value::Int(42);
-        let v2 = Value::Float(PI);
-        let _v3 = Value::Bool(true);
-        let _v4 = Value::String("hello".to_string());
+/// Total order on floats where NaN is greatest and `-0.0 == 0.0`
+fn cmp_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap(),
+    }
+}
 
-        assert_eq!(v1, Value::Int(42));
-        assert_eq!(v2, Value::Float(PI));
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => cmp_floats(*a, *b) == Ordering::Equal,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::None, Value::None) => true,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            (Value::List(a), Value::List(b)) | (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Dict(a), Value::Dict(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, value)| other.dict_get(key) == Some(value))
+            }
+            (Value::Ref(a), Value::Ref(b)) => a == b,
+            _ => false,
+        }
     }
+}
 
-    #[test]
-    fn test_class_instance() {
-        let mut instance = ClassInstance::new("Point".to_string());
-        instance.set_field("x".to_string(), Value::Int(10));
-        instance.set_field("y".to_string(), Value::Int(20));
+impl Eq for Value {}
 
-        assert_eq!(instance.get_field("x"), Some(&Value::Int(10)));
-        assert_eq!(instance.get_field("y"), Some(&Value::Int(20)));
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    #[test]
-    fn test_object() {
-        let obj = Object::new(Value::Int(42));
-        assert_eq!(obj.as_int(), Some(42));
-        assert_eq!(obj.as_float(), Some(42.0));
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => cmp_floats(*a, *b),
+            (Value::Int(a), Value::Float(b)) => cmp_floats(*a as f64, *b).then(Ordering::Less),
+            (Value::Float(a), Value::Int(b)) => cmp_floats(*a, *b as f64).then(Ordering::Greater),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::Object(a), Value::Object(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) | (Value::Array(a), Value::Array(b)) => a.cmp(b),
+            (Value::Tuple(a), Value::Tuple(b)) => a.cmp(b),
+            (Value::Dict(_), Value::Dict(_)) => sorted_entries(self).cmp(&sorted_entries(other)),
+            (Value::Ref(a), Value::Ref(b)) => a.cmp(b),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+/// Dict entries sorted by key, so dicts compare and hash independently of
+/// insertion order
+fn sorted_entries(value: &Value) -> Vec<&(Value, Value)> {
+    let mut entries: Vec<_> = match value {
+        Value::Dict(entries) => entries.iter().collect(),
+        _ => Vec::new(),
+    };
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// Hashing agrees with equality: NaNs hash alike and `-0.0` hashes as `0.0`.
+/// `Int(1)` and `Float(1.0)` are intentionally not equal, so they hash
+/// differently and stay distinct map keys
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Int(n) => n.hash(state),
+            Value::Float(f) => {
+                let canonical = if f.is_nan() {
+                    f64::NAN
+                } else if *f == 0.0 {
+                    0.0
+                } else {
+                    *f
+                };
+                canonical.to_bits().hash(state);
+            }
+            Value::Bool(b) => b.hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Bytes(bytes) => bytes.hash(state),
+            Value::None => {}
+            Value::Object(instance) => instance.hash(state),
+            Value::List(items) | Value::Array(items) => items.hash(state),
+            Value::Tuple(items) => items.hash(state),
+            Value::Dict(_) => sorted_entries(self).hash(state),
+            Value::Ref(handle) => handle.hash(state),
+        }
+    }
+}
+
+/// Python-style formatting for REPL output and error messages
+///
+/// Top-level strings print without quotes, while strings nested in lists or
+/// objects are quoted. Objects print as `ClassName(field=value, ...)` with
+/// fields in name order, tuples as `(a, b)` and dicts as `{key: value, ...}`
+/// in insertion order. Byte buffers print as `b'...'` with non-printable bytes
+/// escaped.
+/// The alternate form `{:#}` puts each element of a list, dict or object on
+/// its own indented line
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => f.write_str(s),
+            _ => write_value(f, self, 0),
+        }
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Value, depth: usize) -> fmt::Result {
+    match value {
+        Value::Int(n) => write!(f, "{}", n),
+        Value::Float(x) => write!(f, "{:?}", x),
+        Value::Bool(true) => f.write_str("True"),
+        Value::Bool(false) => f.write_str("False"),
+        Value::String(s) => write!(f, "{:?}", s),
+        Value::Bytes(bytes) => write!(f, "b'{}'", bytes.escape_ascii()),
+        Value::None => f.write_str("None"),
+        Value::Ref(handle) => write!(f, "<ref #{}>", handle.index()),
+        Value::List(items) | Value::Array(items) => {
+            let items = items.iter().map(|item| (Label::None, item));
+            write_items(f, "[", items, "]", depth)
+        }
+        // A one-element tuple keeps its trailing comma, as in `(1,)`
+        Value::Tuple(items) if items.len() == 1 && !f.alternate() => {
+            f.write_str("(")?;
+            write_value(f, &items[0], depth + 1)?;
+            f.write_str(",)")
+        }
+        Value::Tuple(items) => {
+            let items = items.iter().map(|item| (Label::None, item));
+            write_items(f, "(", items, ")", depth)
+        }
+        Value::Dict(entries) => {
+            let entries = entries.iter().map(|(key, value)| (Label::Key(key), value));
+            write_items(f, "{", entries, "}", depth)
+        }
+        Value::Object(instance) => {
+            let fields = instance
+                .sorted_fields()
+                .into_iter()
+                .map(|(name, value)| (Label::Field(name), value));
+            let open = format!("{}(", instance.class_name);
+            write_items(f, &open, fields, ")", depth)
+        }
+    }
+}
+
+/// What precedes an element when formatting a container
+enum Label<'a> {
+    None,
+    Field(&'a str),
+    Key(&'a Value),
+}
+
+fn write_items<'a>(
+    f: &mut fmt::Formatter<'_>,
+    open: &str,
+    items: impl ExactSizeIterator<Item = (Label<'a>, &'a Value)>,
+    close: &str,
+    depth: usize,
+) -> fmt::Result {
+    const INDENT: &str = "    ";
+
+    f.write_str(open)?;
+    let pretty = f.alternate() && items.len() > 0;
+    for (index, (label, item)) in items.enumerate() {
+        if pretty {
+            f.write_str("\n")?;
+            for _ in 0..=depth {
+                f.write_str(INDENT)?;
+            }
+        } else if index > 0 {
+            f.write_str(", ")?;
+        }
+        match label {
+            Label::None => {}
+            Label::Field(name) => write!(f, "{}=", name)?,
+            Label::Key(key) => {
+                write_value(f, key, depth + 1)?;
+                f.write_str(": ")?;
+            }
+        }
+        write_value(f, item, depth + 1)?;
+        if pretty {
+            f.write_str(",")?;
+        }
+    }
+    if pretty {
+        f.write_str("\n")?;
+        for _ in 0..depth {
+            f.write_str(INDENT)?;
+        }
+    }
+    f.write_str(close)
+}
+
+fn collect_diffs(
+    path: String,
+    left: &Value,
+    right: &Value,
+    epsilon: Option<f64>,
+    diffs: &mut Vec<ValueDiff>,
+) {
+    match (left, right) {
+        (Value::List(a), Value::List(b)) | (Value::Array(a), Value::Array(b)) => {
+            collect_item_diffs(&path, a, b, epsilon, diffs);
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => collect_item_diffs(&path, a, b, epsilon, diffs),
+        (Value::Dict(a), Value::Dict(b)) => {
+            // Keys in the left dict's order, then those only in the right one
+            let keys = a.iter().map(|(key, _)| key).chain(
+                b.iter()
+                    .map(|(key, _)| key)
+                    .filter(|key| left.dict_get(key).is_none()),
+            );
+            for key in keys {
+                let path = match key {
+                    Value::String(s) => format!("{}[{:?}]", path, s),
+                    key => format!("{}[{}]", path, key),
+                };
+                match (left.dict_get(key), right.dict_get(key)) {
+                    (Some(x), Some(y)) => collect_diffs(path, x, y, epsilon, diffs),
+                    (x, y) => diffs.push(ValueDiff {
+                        path,
+                        left: x.cloned(),
+                        right: y.cloned(),
+                    }),
+                }
+            }
+        }
+        (Value::Object(a), Value::Object(b)) if a.class_name == b.class_name => {
+            let mut names: Vec<&String> = a.fields.keys().chain(b.fields.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let path = format!("{}.{}", path, name);
+                match (a.fields.get(name), b.fields.get(name)) {
+                    (Some(x), Some(y)) => collect_diffs(path, x, y, epsilon, diffs),
+                    (x, y) => diffs.push(ValueDiff {
+                        path,
+                        left: x.cloned(),
+                        right: y.cloned(),
+                    }),
+                }
+            }
+        }
+        (Value::Float(a), Value::Float(b)) if epsilon.is_some_and(|eps| (a - b).abs() <= eps) => {}
+        (Value::Float(a), Value::Float(b)) => {
+            // IEEE comparison rather than `Value` equality, so NaN is reported
+            if a != b {
+                diffs.push(ValueDiff {
+                    path,
+                    left: Some(left.clone()),
+                    right: Some(right.clone()),
+                });
+            }
+        }
+        _ => {
+            if left != right {
+                diffs.push(ValueDiff {
+                    path,
+                    left: Some(left.clone()),
+                    right: Some(right.clone()),
+                });
+            }
+        }
+    }
+}
+
+fn collect_item_diffs(
+    path: &str,
+    a: &[Value],
+    b: &[Value],
+    epsilon: Option<f64>,
+    diffs: &mut Vec<ValueDiff>,
+) {
+    for index in 0..a.len().max(b.len()) {
+        let path = format!("{}[{}]", path, index);
+        match (a.get(index), b.get(index)) {
+            (Some(x), Some(y)) => collect_diffs(path, x, y, epsilon, diffs),
+            (x, y) => diffs.push(ValueDiff {
+                path,
+                left: x.cloned(),
+                right: y.cloned(),
+            }),
+        }
+    }
+}
+
+fn merge_values(base: &Value, overlay: &Value, lists: ListMerge) -> Value {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            let mut merged = base.clone();
+            for (name, value) in &overlay.fields {
+                let value = match base.fields.get(name) {
+                    Some(existing) => merge_values(existing, value, lists),
+                    None => value.clone(),
+                };
+                merged.fields.insert(name.clone(), value);
+            }
+            Value::Object(merged)
+        }
+        (Value::List(base), Value::List(overlay)) if lists == ListMerge::Concat => {
+            Value::List(base.iter().chain(overlay).cloned().collect())
+        }
+        (Value::Array(base), Value::Array(overlay)) if lists == ListMerge::Concat => {
+            Value::Array(base.iter().chain(overlay).cloned().collect())
+        }
+        _ => overlay.clone(),
+    }
+}
+
+/// Class instance - stores field values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassInstance {
+    pub class_name: String,
+    pub fields: HashMap<String, Value>,
+}
+
+impl ClassInstance {
+    pub fn new(class_name: String) -> Self {
+        Self {
+            class_name,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Create an instance populated from `(field_name, value)` pairs
+    pub fn from_fields(
+        class_name: String,
+        fields: impl IntoIterator<Item = (String, Value)>,
+    ) -> Self {
+        Self {
+            class_name,
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+
+    pub fn set_field(&mut self, name: String, value: Value) {
+        self.fields.insert(name, value);
+    }
+
+    /// Get a field through nested objects with a dotted path such as `a.b.c`
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let mut value = self.fields.get(segments.next()?)?;
+        for name in segments {
+            match value {
+                Value::Object(child) => value = child.fields.get(name)?,
+                _ => return None,
+            }
+        }
+        Some(value)
+    }
+
+    /// Set a field through nested objects with a dotted path such as `a.b.c`
+    /// Every intermediate segment must already be an object; missing ones are
+    /// an error rather than being created. The final field may be new
+    pub fn set_path(&mut self, path: &str, value: Value) -> Result<(), PathError> {
+        let mut instance = self;
+        let mut segments = path.split('.').peekable();
+        let mut end = 0;
+        while let Some(name) = segments.next() {
+            end += name.len();
+            if segments.peek().is_none() {
+                instance.fields.insert(name.to_string(), value);
+                return Ok(());
+            }
+            instance = match instance.fields.get_mut(name) {
+                Some(Value::Object(child)) => child,
+                Some(_) => {
+                    return Err(PathError::NotAnObject {
+                        path: path[..end].to_string(),
+                    })
+                }
+                None => {
+                    return Err(PathError::MissingField {
+                        path: path[..end].to_string(),
+                    })
+                }
+            };
+            end += 1;
+        }
+        unreachable!("split always yields at least one segment")
+    }
+}
+
+impl ClassInstance {
+    pub(crate) fn sorted_fields(&self) -> Vec<(&String, &Value)> {
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        fields
+    }
+}
+
+/// Instances order by class name, then by their fields sorted by name
+impl PartialOrd for ClassInstance {
+    fn partial_cmp(&self, other: &ClassInstance) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ClassInstance {
+    fn cmp(&self, other: &ClassInstance) -> Ordering {
+        self.class_name
+            .cmp(&other.class_name)
+            .then_with(|| self.sorted_fields().cmp(&other.sorted_fields()))
+    }
+}
+
+/// Fields are hashed in name order so equal instances hash alike regardless
+/// of map iteration order
+impl Hash for ClassInstance {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.class_name.hash(state);
+        self.sorted_fields().hash(state);
+    }
+}
+
+impl Extend<(String, Value)> for ClassInstance {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        self.fields.extend(iter);
+    }
+}
+
+impl Traceable for Value {
+    fn trace_refs(&self, tracer: &mut dyn FnMut(GcRef)) {
+        match self {
+            Value::Ref(handle) => tracer(*handle),
+            Value::Object(instance) => instance.trace_refs(tracer),
+            Value::List(items) | Value::Array(items) => {
+                for item in items {
+                    item.trace_refs(tracer);
+                }
+            }
+            Value::Tuple(items) => {
+                for item in items.iter() {
+                    item.trace_refs(tracer);
+                }
+            }
+            Value::Dict(entries) => {
+                for (key, value) in entries {
+                    key.trace_refs(tracer);
+                    value.trace_refs(tracer);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Traceable for ClassInstance {
+    fn trace_refs(&self, tracer: &mut dyn FnMut(GcRef)) {
+        for value in self.fields.values() {
+            value.trace_refs(tracer);
+        }
+    }
+}
+
+/// Read-only view of a value that can be handed to untrusted code
+///
+/// Only non-mutating accessors are exposed, so holders can inspect but
+/// never modify the underlying value:
+///
+/// ```compile_fail
+/// use pain_runtime::{Value, ValueRef};
+///
+/// let value = Value::List(vec![Value::Int(1)]);
+/// let view = ValueRef::new(&value);
+/// view.push(Value::Int(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueRef<'a> {
+    value: &'a Value,
+}
+
+impl<'a> ValueRef<'a> {
+    pub fn new(value: &'a Value) -> Self {
+        Self { value }
+    }
+
+    /// Get the element at `index` of a list or array
+    pub fn index(&self, index: usize) -> Option<ValueRef<'a>> {
+        match self.value {
+            Value::List(items) | Value::Array(items) => items.get(index).map(ValueRef::new),
+            _ => None,
+        }
+    }
+
+    /// Get the number of elements of a list or array
+    pub fn len(&self) -> Option<usize> {
+        match self.value {
+            Value::List(items) | Value::Array(items) => Some(items.len()),
+            _ => None,
+        }
+    }
+
+    /// Check whether a list or array has no elements
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Iterate the elements of a list or array
+    pub fn iter(&self) -> Option<impl Iterator<Item = ValueRef<'a>>> {
+        match self.value {
+            Value::List(items) | Value::Array(items) => Some(items.iter().map(ValueRef::new)),
+            _ => None,
+        }
+    }
+
+    /// Get a field of an object
+    pub fn get_field(&self, name: &str) -> Option<ValueRef<'a>> {
+        match self.value {
+            Value::Object(instance) => instance.get_field(name).map(ValueRef::new),
+            _ => None,
+        }
+    }
+
+    /// Get the class name of an object
+    pub fn class_name(&self) -> Option<&'a str> {
+        match self.value {
+            Value::Object(instance) => Some(&instance.class_name),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self.value {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self.value {
+            Value::Float(f) => Some(*f),
+            Value::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.value {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self.value, Value::None)
+    }
+
+    /// Clone the viewed value into an owned value
+    pub fn to_owned_value(&self) -> Value {
+        self.value.clone()
+    }
+}
+
+/// Runtime object representation
+pub struct Object {
+    pub value: Value,
+    // Future: metadata, type info, etc.
+}
+
+impl Object {
+    pub fn new(value: Value) -> Self {
+        Self { value }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match &self.value {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match &self.value {
+            Value::Float(f) => Some(*f),
+            Value::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match &self.value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&String> {
+        match &self.value {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match &self.value {
+            Value::List(v) | Value::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Errors raised while executing runtime operations
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RuntimeError {
+    #[error("function `{name}` expects {expected} arguments, got {got}")]
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    #[error("unknown native function")]
+    UnknownFunction,
+    #[error("unknown class `{0}`")]
+    UnknownClass(String),
+    #[error("inheritance cycle through class `{0}`")]
+    InheritanceCycle(String),
+    #[error(transparent)]
+    Type(#[from] TypeError),
+    #[error(transparent)]
+    Value(#[from] ValueError),
+    #[error("{0}")]
+    Native(String),
+}
+
+/// Signature of a host function callable from the runtime
+pub type NativeFn = dyn Fn(&mut Runtime, &[Value]) -> Result<Value, RuntimeError>;
+
+/// Reference to a registered native function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FunctionRef {
+    index: usize,
+}
+
+struct NativeFunction {
+    name: String,
+    arity: usize,
+    func: Rc<NativeFn>,
+}
+
+/// Reference to a method implementation
+/// Methods are native functions that receive the instance as their first argument
+pub type MethodRef = FunctionRef;
+
+/// Class definition holding the method table and default field values for
+/// instances of `name`, with an optional single parent class
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDef {
+    name: String,
+    parent: Option<String>,
+    methods: HashMap<String, MethodRef>,
+    fields: HashMap<String, Value>,
+}
+
+impl ClassDef {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            parent: None,
+            methods: HashMap::new(),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Inherit methods and default fields from `parent`
+    pub fn with_parent(mut self, parent: &str) -> Self {
+        self.parent = Some(parent.to_string());
+        self
+    }
+
+    /// Add or replace a field default copied into new instances
+    pub fn with_field(mut self, name: &str, default: Value) -> Self {
+        self.fields.insert(name.to_string(), default);
+        self
+    }
+
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+
+    /// Add or replace a method
+    pub fn with_method(mut self, name: &str, method: MethodRef) -> Self {
+        self.methods.insert(name.to_string(), method);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Look up a method defined directly on this class
+    pub fn method(&self, name: &str) -> Option<MethodRef> {
+        self.methods.get(name).copied()
+    }
+}
+
+/// Id of a string interned with `Runtime::intern`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InternId(u32);
+
+const INTERNER_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Default nesting limit for the runtime's depth-checked `Value` operations
+const DEFAULT_MAX_VALUE_DEPTH: usize = 256;
+
+/// Deduplicating string table whose bytes live in a dedicated arena, so
+/// resetting the runtime arena never invalidates interned strings
+struct Interner {
+    // Both borrow from `arena` and are declared first so they drop first
+    ids: HashMap<&'static str, InternId>,
+    strings: Vec<&'static str>,
+    arena: Arena,
+}
+
+impl Interner {
+    fn new() -> Result<Self, &'static str> {
+        Ok(Self {
+            ids: HashMap::new(),
+            strings: Vec::new(),
+            arena: Arena::with_pools(INTERNER_CHUNK_SIZE, &[])?,
+        })
+    }
+
+    fn intern(&mut self, s: &str) -> InternId {
+        if let Some(&id) = self.ids.get(s) {
+            return id;
+        }
+
+        let stored: &'static str = if s.is_empty() {
+            ""
+        } else {
+            let ptr = self
+                .arena
+                .allocate(s.len(), 1)
+                .expect("out of memory interning a string");
+            // The arena is never reset, and the `'static` borrow never escapes
+            // past `&self`, so the copy stays valid as long as it is reachable
+            unsafe {
+                ptr::copy_nonoverlapping(s.as_ptr(), ptr.as_ptr(), s.len());
+                std::str::from_utf8_unchecked(std::slice::from_raw_parts(ptr.as_ptr(), s.len()))
+            }
+        };
+        let id = InternId(u32::try_from(self.strings.len()).expect("too many interned strings"));
+        self.strings.push(stored);
+        self.ids.insert(stored, id);
+        id
+    }
+
+    fn resolve(&self, id: InternId) -> Option<&str> {
+        self.strings.get(id.0 as usize).copied()
+    }
+}
+
+/// Snapshot of a runtime's arena and GC memory usage
+/// The GC fields are zero under `GcStrategy::None`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RuntimeMetrics {
+    pub used: usize,            // Arena bytes in use
+    pub capacity: usize,        // Arena bytes reserved
+    pub allocator_count: usize, // Arena backing allocators
+    pub total_allocated: usize, // GC bytes allocated
+    pub object_count: usize,    // GC objects tracked
+    pub live_count: usize,      // GC objects live after the last collection
+    pub collection_count: usize,
+    pub last_pause: Duration,
+}
+
+/// Callback run with the requested size of each runtime allocation
+pub type AllocateHook = dyn FnMut(usize);
+
+/// Callback run with the runtime's metrics after each collection
+pub type CollectHook = dyn FnMut(&RuntimeMetrics);
+
+/// Handle to a value in a runtime's object slab, created by `Runtime::new_object`
+/// A handle goes stale once its object is freed, even if the slot is reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+/// Object slab entry; the generation is bumped each time the slot is freed
+#[derive(Debug, Default)]
+struct Slot {
+    generation: u32,
+    value: Option<Value>,
+}
+
+thread_local! {
+    /// Runtime installed on this thread with `Runtime::install`
+    static CURRENT: RefCell<Option<Runtime>> = const { RefCell::new(None) };
+}
+
+/// Runtime context for managing objects and memory
+pub struct Runtime {
+    arena: Arena,
+    gc: Option<Box<dyn Collector>>, // None under `GcStrategy::None`
+    natives: Vec<NativeFunction>,
+    classes: HashMap<String, ClassDef>,
+    interner: Interner,
+    max_value_depth: usize,
+    objects: Vec<Slot>,
+    free_slots: Vec<u32>, // Indices of empty slots in `objects`
+    allocate_hooks: Vec<Box<AllocateHook>>,
+    collect_hooks: Vec<Box<CollectHook>>,
+}
+
+impl Runtime {
+    /// Create a new runtime instance
+    pub fn new() -> Result<Self, &'static str> {
+        Self::from_parts(
+            Arena::new(1024 * 1024)?, // 1MB default
+            Some(Box::new(GarbageCollector::new())),
+        )
+    }
+
+    /// Create a new runtime with custom arena size
+    pub fn with_arena_size(size: usize) -> Result<Self, &'static str> {
+        Self::from_parts(Arena::new(size)?, Some(Box::new(GarbageCollector::new())))
+    }
+
+    /// Create a new runtime with GC enabled and custom threshold
+    pub fn with_gc_threshold(threshold: usize) -> Result<Self, &'static str> {
+        Self::from_parts(
+            Arena::new(1024 * 1024)?,
+            Some(Box::new(GarbageCollector::with_threshold(threshold))),
+        )
+    }
+
+    /// Create a new runtime using the given GC strategy, collecting once
+    /// `threshold` bytes are allocated (ignored under `GcStrategy::None`)
+    pub fn with_strategy(strategy: GcStrategy, threshold: usize) -> Result<Self, &'static str> {
+        Self::from_parts(Arena::new(1024 * 1024)?, strategy.build(threshold))
+    }
+
+    fn from_parts(arena: Arena, gc: Option<Box<dyn Collector>>) -> Result<Self, &'static str> {
+        Ok(Self {
+            arena,
+            gc,
+            natives: Vec::new(),
+            classes: HashMap::new(),
+            interner: Interner::new()?,
+            max_value_depth: DEFAULT_MAX_VALUE_DEPTH,
+            objects: Vec::new(),
+            free_slots: Vec::new(),
+            allocate_hooks: Vec::new(),
+            collect_hooks: Vec::new(),
+        })
+    }
+
+    /// Register a host function taking exactly `arity` arguments
+    pub fn register_native(&mut self, name: &str, arity: usize, f: Box<NativeFn>) -> FunctionRef {
+        let index = self.natives.len();
+        self.natives.push(NativeFunction {
+            name: name.to_string(),
+            arity,
+            func: Rc::from(f),
+        });
+        FunctionRef { index }
+    }
+
+    /// Find the most recently registered native function with this name
+    pub fn lookup_native(&self, name: &str) -> Option<FunctionRef> {
+        self.natives
+            .iter()
+            .rposition(|native| native.name == name)
+            .map(|index| FunctionRef { index })
+    }
+
+    /// Call a native function after checking the argument count
+    pub fn call_native(
+        &mut self,
+        func: &FunctionRef,
+        args: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        let native = self
+            .natives
+            .get(func.index)
+            .ok_or(RuntimeError::UnknownFunction)?;
+        if args.len() != native.arity {
+            return Err(RuntimeError::ArityMismatch {
+                name: native.name.clone(),
+                expected: native.arity,
+                got: args.len(),
+            });
+        }
+
+        // Clone the handle so the function can borrow the runtime mutably
+        let f = Rc::clone(&native.func);
+        f(self, args)
+    }
+
+    /// Register a class, replacing any previous class with the same name
+    pub fn register_class(&mut self, class: ClassDef) {
+        self.classes.insert(class.name.clone(), class);
+    }
+
+    pub fn class(&self, name: &str) -> Option<&ClassDef> {
+        self.classes.get(name)
+    }
+
+    /// Find a method defined directly on a registered class
+    pub fn lookup_method(&self, class_name: &str, method: &str) -> Option<MethodRef> {
+        self.classes.get(class_name)?.method(method)
+    }
+
+    /// Find a method on a class or the nearest ancestor defining it
+    pub fn resolve_method(
+        &self,
+        class_name: &str,
+        method: &str,
+    ) -> Result<Option<MethodRef>, RuntimeError> {
+        Ok(self
+            .ancestry(class_name)?
+            .into_iter()
+            .find_map(|class| class.method(method)))
+    }
+
+    /// Check whether `class_name` is `ancestor` or inherits from it
+    pub fn is_subclass_of(&self, class_name: &str, ancestor: &str) -> Result<bool, RuntimeError> {
+        Ok(self
+            .ancestry(class_name)?
+            .iter()
+            .any(|class| class.name == ancestor))
+    }
+
+    /// Create an instance of a registered class populated with the field
+    /// defaults of the class and its ancestors, nearest definition winning
+    pub fn new_instance(&self, class_name: &str) -> Result<ClassInstance, RuntimeError> {
+        let mut instance = ClassInstance::new(class_name.to_string());
+        for class in self.ancestry(class_name)?.iter().rev() {
+            instance.extend(class.fields.clone());
+        }
+        Ok(instance)
+    }
+
+    /// The class followed by its ancestors, nearest first
+    fn ancestry(&self, class_name: &str) -> Result<Vec<&ClassDef>, RuntimeError> {
+        let mut chain: Vec<&ClassDef> = Vec::new();
+        let mut next = Some(class_name);
+        while let Some(name) = next {
+            if chain.iter().any(|class| class.name == name) {
+                return Err(RuntimeError::InheritanceCycle(name.to_string()));
+            }
+            let class = self
+                .classes
+                .get(name)
+                .ok_or_else(|| RuntimeError::UnknownClass(name.to_string()))?;
+            chain.push(class);
+            next = class.parent();
+        }
+        Ok(chain)
+    }
+
+    /// Intern a string, returning the same id for every equal string
+    pub fn intern(&mut self, s: &str) -> InternId {
+        self.interner.intern(s)
+    }
+
+    /// Get the string behind an id from this runtime's `intern`
+    pub fn resolve(&self, id: InternId) -> Option<&str> {
+        self.interner.resolve(id)
+    }
+
+    /// Allocate memory in the runtime arena
+    pub fn allocate(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        self.notify_allocate(size);
+        self.arena.allocate(size, align)
+    }
+
+    /// Allocate memory for a `Layout` in the runtime arena
+    pub fn allocate_layout(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.notify_allocate(layout.size());
+        self.arena.allocate_layout(layout)
+    }
+
+    /// Move a value into the runtime arena and return a pointer to it
+    /// `reset` does not drop arena values, so strings, lists and other buffers
+    /// they own leak unless the value is released with `dealloc_value` first
+    pub fn alloc_value(&mut self, value: Value) -> Option<NonNull<Value>> {
+        let ptr = self
+            .allocate_layout(Layout::new::<Value>())?
+            .cast::<Value>();
+        unsafe { ptr.as_ptr().write(value) };
+        Some(ptr)
+    }
+
+    /// Drop a value created by `alloc_value` and hand its memory back to the arena
+    ///
+    /// # Safety
+    /// `ptr` must come from `alloc_value` on this runtime since the last
+    /// `reset`, must not have been released already, and must not be used after
+    pub unsafe fn dealloc_value(&mut self, ptr: NonNull<Value>) {
+        ptr.as_ptr().drop_in_place();
+        self.arena.deallocate(ptr.cast(), mem::size_of::<Value>());
+    }
+
+    /// Get the nesting limit of `deep_clone`, `deep_eq` and `deep_size`
+    pub fn max_value_depth(&self) -> usize {
+        self.max_value_depth
+    }
+
+    /// Set the nesting limit of `deep_clone`, `deep_eq` and `deep_size`
+    /// Each level takes stack space, so a high limit can still overflow it
+    pub fn set_max_value_depth(&mut self, depth: usize) {
+        self.max_value_depth = depth;
+    }
+
+    /// `Value::deep_clone`, failing with `DepthExceeded` past `max_value_depth`
+    pub fn deep_clone(&self, value: &Value) -> Result<Value, ValueError> {
+        value.clone_within(DepthLimit(self.max_value_depth), 0)
+    }
+
+    /// `Value::structural_eq`, failing with `DepthExceeded` past `max_value_depth`
+    pub fn deep_eq(&self, left: &Value, right: &Value) -> Result<bool, ValueError> {
+        left.structural_eq_within(right, DepthLimit(self.max_value_depth), 0)
+    }
+
+    /// `Value::heap_size`, failing with `DepthExceeded` past `max_value_depth`
+    pub fn deep_size(&self, value: &Value) -> Result<usize, ValueError> {
+        value.heap_size_within(DepthLimit(self.max_value_depth))
+    }
+
+    /// Store a value in the runtime's object slab and return a handle to it
+    /// The value stays alive until `free_object` is called with the handle
+    pub fn new_object(&mut self, value: Value) -> Handle {
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None => {
+                let index = u32::try_from(self.objects.len()).expect("too many objects");
+                self.objects.push(Slot::default());
+                index
+            }
+        };
+        let slot = &mut self.objects[index as usize];
+        slot.value = Some(value);
+        Handle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    /// Get the value behind a handle, or None if it was freed
+    pub fn get(&self, handle: Handle) -> Option<&Value> {
+        self.objects
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)?
+            .value
+            .as_ref()
+    }
+
+    /// Get the value behind a handle mutably, or None if it was freed
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut Value> {
+        self.objects
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)?
+            .value
+            .as_mut()
+    }
+
+    /// Remove the value behind a handle and return it, or None if it was
+    /// already freed. The handle and its copies go stale
+    pub fn free_object(&mut self, handle: Handle) -> Option<Value> {
+        let slot = self
+            .objects
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)?;
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+        Some(value)
+    }
+
+    /// Capture the runtime arena's memory for a later `restore`
+    /// The GC heap and interned strings are not included
+    pub fn snapshot(&self) -> ArenaSnapshot {
+        self.arena.snapshot()
+    }
+
+    /// Roll the runtime arena back to a snapshot
+    /// Pointers handed out after the snapshot was taken become invalid, and
+    /// values moved in with `alloc_value` since then must not be released
+    pub fn restore(&mut self, snapshot: &ArenaSnapshot) -> Result<(), &'static str> {
+        self.arena.restore(snapshot)
+    }
+
+    /// Reset the runtime arena (free all allocations)
+    /// GC roots pointing into the arena are unregistered, since the memory
+    /// they name will be handed out again
+    pub fn reset(&mut self) {
+        if let Some(gc) = self.gc.as_mut() {
+            let arena = &self.arena;
+            gc.retain_roots(&mut |ptr| NonNull::new(ptr).is_none_or(|ptr| !arena.contains(ptr)));
+        }
+        self.arena.reset();
+    }
+
+    /// Unregister every GC root, collect the now unreachable GC objects and
+    /// reset the arena, leaving no pointer into freed memory behind
+    pub fn reset_and_clear_gc(&mut self) {
+        if let Some(gc) = self.gc.as_mut() {
+            gc.retain_roots(&mut |_| false);
+            gc.collect();
+            self.notify_collect();
+        }
+        self.arena.reset();
+    }
+
+    /// Register a pointer as a GC root; a no-op without a collector
+    pub fn add_root(&mut self, ptr: *mut u8) {
+        if let Some(gc) = self.gc.as_mut() {
+            gc.add_root(ptr);
+        }
+    }
+
+    /// Unregister a GC root
+    pub fn remove_root(&mut self, ptr: *mut u8) {
+        if let Some(gc) = self.gc.as_mut() {
+            gc.remove_root(ptr);
+        }
+    }
+
+    /// Get the number of registered GC roots
+    pub fn root_count(&self) -> usize {
+        self.gc.as_ref().map_or(0, |gc| gc.root_count())
+    }
+
+    /// Get memory usage statistics
+    pub fn memory_stats(&self) -> (usize, usize) {
+        (self.arena.total_used(), self.arena.total_capacity())
+    }
+
+    /// Get arena and GC memory metrics in one snapshot
+    pub fn metrics(&self) -> RuntimeMetrics {
+        let mut metrics = RuntimeMetrics {
+            used: self.arena.total_used(),
+            capacity: self.arena.total_capacity(),
+            allocator_count: self.arena.allocators_info().count(),
+            ..RuntimeMetrics::default()
+        };
+        if let Some(gc) = &self.gc {
+            (
+                metrics.total_allocated,
+                metrics.object_count,
+                metrics.live_count,
+            ) = gc.stats();
+            metrics.collection_count = gc.collection_count();
+            metrics.last_pause = gc.last_pause();
+        }
+        metrics
+    }
+
+    /// Install a callback run with the requested size before every arena
+    /// allocation, including failed ones
+    pub fn on_allocate(&mut self, hook: Box<AllocateHook>) {
+        self.allocate_hooks.push(hook);
+    }
+
+    /// Install a callback run with the runtime's metrics after every collection
+    pub fn on_collect(&mut self, hook: Box<CollectHook>) {
+        self.collect_hooks.push(hook);
+    }
+
+    fn notify_allocate(&mut self, size: usize) {
+        for hook in &mut self.allocate_hooks {
+            hook(size);
+        }
+    }
+
+    fn notify_collect(&mut self) {
+        if self.collect_hooks.is_empty() {
+            return;
+        }
+        let metrics = self.metrics();
+        for hook in &mut self.collect_hooks {
+            hook(&metrics);
+        }
+    }
+
+    /// Run garbage collection and return the bytes freed; a no-op without a collector
+    pub fn collect_garbage(&mut self) -> usize {
+        let Some(gc) = self.gc.as_mut() else {
+            return 0;
+        };
+        let freed = gc.force_collect();
+        self.notify_collect();
+        freed
+    }
+
+    /// Get GC statistics, all zero without a collector
+    pub fn gc_stats(&self) -> (usize, usize, usize) {
+        self.gc.as_ref().map_or((0, 0, 0), |gc| gc.stats())
+    }
+
+    /// Manually trigger GC collection
+    pub fn gc_collect(&mut self) {
+        if let Some(gc) = self.gc.as_mut() {
+            gc.collect();
+            self.notify_collect();
+        }
+    }
+}
+
+impl Runtime {
+    /// Make this runtime the current one for the calling thread, returning
+    /// the runtime it replaces
+    ///
+    /// # Panics
+    /// Panics if called from inside `with_current`
+    pub fn install(self) -> Option<Runtime> {
+        CURRENT.with(|current| {
+            current
+                .try_borrow_mut()
+                .expect("Runtime::install called inside Runtime::with_current")
+                .replace(self)
+        })
+    }
+
+    /// Take the current runtime off the calling thread and return it, or
+    /// None if none is installed
+    ///
+    /// # Panics
+    /// Panics if called from inside `with_current`
+    pub fn uninstall() -> Option<Runtime> {
+        CURRENT.with(|current| {
+            current
+                .try_borrow_mut()
+                .expect("Runtime::uninstall called inside Runtime::with_current")
+                .take()
+        })
+    }
+
+    /// Run `f` with the runtime installed on the calling thread
+    ///
+    /// The runtime stays mutably borrowed while `f` runs, so `f` must not
+    /// call `with_current`, `install` or `uninstall` itself; pass the
+    /// `&mut Runtime` it receives down instead
+    ///
+    /// # Panics
+    /// Panics if no runtime is installed or when called reentrantly
+    pub fn with_current<R>(f: impl FnOnce(&mut Runtime) -> R) -> R {
+        CURRENT.with(|current| {
+            let mut current = current
+                .try_borrow_mut()
+                .expect("Runtime::with_current called reentrantly");
+            f(current
+                .as_mut()
+                .expect("no Runtime installed on this thread"))
+        })
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new().expect("Failed to create default runtime")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value() {
+        use std::f64::consts::PI;
+
+        let v1 = Value::Int(42);
+        let v2 = Value::Float(PI);
+        let _v3 = Value::Bool(true);
+        let _v4 = Value::String("hello".to_string());
+
+        assert_eq!(v1, Value::Int(42));
+        assert_eq!(v2, Value::Float(PI));
+    }
+
+    #[test]
+    fn test_value_arithmetic() {
+        // Int op Int stays Int
+        assert_eq!(Value::Int(7) + Value::Int(2), Ok(Value::Int(9)));
+        assert_eq!(Value::Int(7) - Value::Int(2), Ok(Value::Int(5)));
+        assert_eq!(Value::Int(7) * Value::Int(2), Ok(Value::Int(14)));
+        assert_eq!(Value::Int(7) / Value::Int(2), Ok(Value::Int(3)));
+        assert_eq!(Value::Int(7) % Value::Int(2), Ok(Value::Int(1)));
+
+        // Any float operand promotes to Float
+        assert_eq!(&Value::Int(1) + &Value::Float(0.5), Ok(Value::Float(1.5)));
+        assert_eq!(&Value::Float(3.0) / &Value::Int(2), Ok(Value::Float(1.5)));
+        assert_eq!(
+            &Value::Float(2.5) * &Value::Float(2.0),
+            Ok(Value::Float(5.0))
+        );
+        assert_eq!(&Value::Float(7.5) % &Value::Int(2), Ok(Value::Float(1.5)));
+        assert_eq!(
+            &Value::Float(1.0) / &Value::Int(0),
+            Ok(Value::Float(f64::INFINITY))
+        );
+
+        // Strings concatenate with + only
+        assert_eq!(
+            Value::String("foo".to_string()) + Value::String("bar".to_string()),
+            Ok(Value::String("foobar".to_string()))
+        );
+        assert_eq!(
+            Value::String("a".to_string()) * Value::String("b".to_string()),
+            Err(ValueError::UnsupportedOperands {
+                op: "*",
+                left: "str",
+                right: "str",
+            })
+        );
+
+        // Mismatched types and integer errors
+        assert_eq!(
+            Value::Int(1) + Value::String("1".to_string()),
+            Err(ValueError::UnsupportedOperands {
+                op: "+",
+                left: "int",
+                right: "str",
+            })
+        );
+        assert_eq!(
+            Value::Int(1) / Value::Int(0),
+            Err(ValueError::DivisionByZero)
+        );
+        assert_eq!(
+            Value::Int(1) % Value::Int(0),
+            Err(ValueError::DivisionByZero)
+        );
+        assert_eq!(
+            Value::Int(i64::MAX) + Value::Int(1),
+            Err(ValueError::Overflow("+"))
+        );
+        assert_eq!(
+            Value::Int(i64::MIN) / Value::Int(-1),
+            Err(ValueError::Overflow("/"))
+        );
+    }
+
+    #[test]
+    fn test_value_ordering() {
+        let mut values = vec![
+            Value::String("b".to_string()),
+            Value::Float(f64::NAN),
+            Value::Int(3),
+            Value::List(vec![Value::Int(1), Value::Int(2)]),
+            Value::Float(1.5),
+            Value::Bool(true),
+            Value::None,
+            Value::Float(f64::INFINITY),
+            Value::String("a".to_string()),
+            Value::Int(1),
+            Value::List(vec![Value::Int(1)]),
+            Value::Float(1.0),
+            Value::Bool(false),
+        ];
+        values.sort();
+
+        assert_eq!(
+            values,
+            vec![
+                Value::None,
+                Value::Bool(false),
+                Value::Bool(true),
+                Value::Int(1),
+                Value::Float(1.0),
+                Value::Float(1.5),
+                Value::Int(3),
+                Value::Float(f64::INFINITY),
+                Value::Float(f64::NAN),
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+                Value::List(vec![Value::Int(1)]),
+                Value::List(vec![Value::Int(1), Value::Int(2)]),
+            ]
+        );
+
+        // NaN is equal to itself and greater than every other float
+        assert_eq!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+        assert!(Value::Float(f64::NAN) > Value::Float(f64::MAX));
+        assert_eq!(Value::Float(-0.0), Value::Float(0.0));
+        assert_ne!(Value::Int(1), Value::Float(1.0));
+
+        // Objects order by class name, then by fields
+        let point = |x: i64| {
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![("x".to_string(), Value::Int(x))],
+            ))
+        };
+        assert!(point(1) < point(2));
+        assert!(Value::Object(ClassInstance::new("Line".to_string())) < point(0));
+
+        let map: std::collections::BTreeMap<Value, &str> =
+            [(Value::Int(2), "two"), (Value::None, "none")].into();
+        assert_eq!(map.keys().next(), Some(&Value::None));
+    }
+
+    #[test]
+    fn test_value_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashSet;
+
+        let hash = |value: &Value| {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(
+            hash(&Value::Float(f64::NAN)),
+            hash(&Value::Float(-f64::NAN))
+        );
+        assert_eq!(hash(&Value::Float(0.0)), hash(&Value::Float(-0.0)));
+        assert_ne!(hash(&Value::Int(1)), hash(&Value::Float(1.0)));
+        assert_ne!(
+            hash(&Value::List(vec![Value::Int(1)])),
+            hash(&Value::Array(vec![Value::Int(1)]))
+        );
+
+        // Field insertion order does not affect the hash
+        let fields = [
+            ("a".to_string(), Value::Int(1)),
+            ("b".to_string(), Value::String("x".to_string())),
+            ("c".to_string(), Value::List(vec![Value::Bool(true)])),
+        ];
+        let forward = Value::Object(ClassInstance::from_fields("T".to_string(), fields.clone()));
+        let backward = Value::Object(ClassInstance::from_fields(
+            "T".to_string(),
+            fields.into_iter().rev(),
+        ));
+        assert_eq!(forward, backward);
+        assert_eq!(hash(&forward), hash(&backward));
+
+        let mut counts: HashMap<Value, usize> = HashMap::new();
+        for value in [
+            forward,
+            backward,
+            Value::Int(1),
+            Value::Float(1.0),
+            Value::Float(f64::NAN),
+            Value::Float(f64::NAN),
+        ] {
+            *counts.entry(value).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&Value::Float(f64::NAN)], 2);
+        assert_eq!(counts[&Value::Int(1)], 1);
+
+        let unique: HashSet<Value> = counts.into_keys().collect();
+        assert!(unique.contains(&Value::Float(1.0)));
+    }
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(Value::String("hi".to_string()).to_string(), "hi");
+        assert_eq!(Value::None.to_string(), "None");
+        assert_eq!(Value::Float(2.0).to_string(), "2.0");
+        assert_eq!(Value::Bool(false).to_string(), "False");
+        assert_eq!(Value::List(vec![]).to_string(), "[]");
+
+        let point = |x: i64, label: &str| {
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![
+                    ("y".to_string(), Value::String(label.to_string())),
+                    ("x".to_string(), Value::Int(x)),
+                ],
+            ))
+        };
+        let value = Value::List(vec![
+            Value::Int(1),
+            point(2, "a"),
+            Value::List(vec![Value::String("b".to_string()), Value::None]),
+        ]);
+
+        assert_eq!(value.to_string(), r#"[1, Point(x=2, y="a"), ["b", None]]"#);
+        assert_eq!(
+            format!("{:#}", value),
+            r#"[
+    1,
+    Point(
+        x=2,
+        y="a",
+    ),
+    [
+        "b",
+        None,
+    ],
+]"#
+        );
+        assert_eq!(
+            format!(
+                "{:#}",
+                Value::Object(ClassInstance::new("Empty".to_string()))
+            ),
+            "Empty()"
+        );
+    }
+
+    #[test]
+    fn test_type_name() {
+        let cases = [
+            (Value::Int(1), "int"),
+            (Value::Float(1.0), "float"),
+            (Value::Bool(true), "bool"),
+            (Value::String("s".to_string()), "str"),
+            (Value::None, "none"),
+            (Value::List(vec![]), "list"),
+            (Value::Array(vec![]), "array"),
+            (
+                Value::Ref(GarbageCollector::new().allocate_ref(8).unwrap()),
+                "ref",
+            ),
+            (
+                Value::Object(ClassInstance::new("Point".to_string())),
+                "Point",
+            ),
+        ];
+        for (value, name) in cases {
+            assert_eq!(value.type_name(), name);
+        }
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        let cases = [
+            (Value::Bool(true), true),
+            (Value::Bool(false), false),
+            (Value::Int(0), false),
+            (Value::Int(-3), true),
+            (Value::Float(0.0), false),
+            (Value::Float(-0.0), false),
+            (Value::Float(0.5), true),
+            (Value::Float(f64::NAN), true),
+            (Value::String(String::new()), false),
+            (Value::String("x".to_string()), true),
+            (Value::List(vec![]), false),
+            (Value::List(vec![Value::None]), true),
+            (Value::Array(vec![]), false),
+            (Value::Array(vec![Value::Int(0)]), true),
+            (Value::None, false),
+            (Value::Object(ClassInstance::new("Empty".to_string())), true),
+        ];
+        for (value, truthy) in cases {
+            assert_eq!(value.is_truthy(), truthy, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_dict() {
+        let mut dict = Value::Dict(Vec::new());
+        assert!(!dict.is_truthy());
+        assert_eq!(dict.len(), Some(0));
+        assert_eq!(dict.type_name(), "dict");
+
+        let name = Value::String("name".to_string());
+        assert_eq!(dict.dict_insert(name.clone(), Value::Int(1)), Ok(None));
+        assert_eq!(dict.dict_insert(Value::Int(2), Value::None), Ok(None));
+        assert!(dict.is_truthy());
+        assert_eq!(dict.dict_get(&name), Some(&Value::Int(1)));
+        assert_eq!(dict.dict_get(&Value::Float(2.0)), None);
+
+        // Overwriting keeps the entry's position
+        assert_eq!(
+            dict.dict_insert(name.clone(), Value::Int(3)),
+            Ok(Some(Value::Int(1)))
+        );
+        assert_eq!(dict.len(), Some(2));
+        assert_eq!(dict.to_string(), r#"{"name": 3, 2: None}"#);
+
+        // Equality ignores insertion order
+        let reordered = Value::Dict(vec![(Value::Int(2), Value::None), (name, Value::Int(3))]);
+        assert_eq!(dict, reordered);
+        assert_eq!(dict.cmp(&reordered), Ordering::Equal);
+
+        assert!(Value::Int(1).dict_insert(Value::None, Value::None).is_err());
+        assert_eq!(Value::Int(1).dict_get(&Value::None), None);
+    }
+
+    #[test]
+    fn test_tuple() {
+        let pair = || Value::Tuple(vec![Value::Int(1), Value::Int(2)].into());
+        assert_eq!(pair(), pair());
+        assert_ne!(pair(), Value::List(vec![Value::Int(1), Value::Int(2)]));
+        assert!(pair() > Value::Tuple(vec![Value::Int(1)].into()));
+
+        assert_eq!(pair().type_name(), "tuple");
+        assert_eq!(pair().len(), Some(2));
+        assert!(pair().is_truthy());
+        assert!(!Value::Tuple(Box::new([])).is_truthy());
+
+        assert_eq!(pair().to_string(), "(1, 2)");
+        assert_eq!(Value::Tuple(vec![Value::Int(1)].into()).to_string(), "(1,)");
+        assert_eq!(Value::Tuple(Box::new([])).to_string(), "()");
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = Value::Bytes(vec![b'h', b'i', 0, 0xff]);
+        assert_eq!(bytes.as_bytes(), Some(&[b'h', b'i', 0, 0xff][..]));
+        assert_eq!(bytes.len(), Some(4));
+        assert_eq!(bytes.byte_at(1), Some(b'i'));
+        assert_eq!(bytes.byte_at(4), None);
+        assert_eq!(Value::Int(1).byte_at(0), None);
+        assert_eq!(bytes.type_name(), "bytes");
+        assert!(!Value::Bytes(vec![]).is_truthy());
+
+        assert_eq!(
+            Value::Bytes(vec![1, 2]) + Value::Bytes(vec![3]),
+            Ok(Value::Bytes(vec![1, 2, 3]))
+        );
+        assert!((Value::Bytes(vec![1]) + Value::String("a".to_string())).is_err());
+
+        assert_eq!(bytes, Value::Bytes(vec![b'h', b'i', 0, 0xff]));
+        assert_ne!(bytes, Value::List(vec![Value::Int(104), Value::Int(105)]));
+        assert_eq!(bytes.to_string(), r"b'hi\x00\xff'");
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(i64::try_from(Value::Int(42)), Ok(42));
+        assert_eq!(f64::try_from(&Value::Float(0.5)), Ok(0.5));
+        assert_eq!(f64::try_from(Value::Int(2)), Ok(2.0));
+        assert_eq!(bool::try_from(&Value::Bool(true)), Ok(true));
+        assert_eq!(
+            String::try_from(Value::String("hi".to_string())),
+            Ok("hi".to_string())
+        );
+
+        assert_eq!(
+            i64::try_from(Value::Float(1.0)),
+            Err(ValueError::TypeMismatch {
+                expected: "int",
+                got: "float".to_string(),
+            })
+        );
+        assert_eq!(
+            f64::try_from(&Value::String("1".to_string())),
+            Err(ValueError::TypeMismatch {
+                expected: "float",
+                got: "str".to_string(),
+            })
+        );
+        assert_eq!(
+            bool::try_from(Value::Int(1)),
+            Err(ValueError::TypeMismatch {
+                expected: "bool",
+                got: "int".to_string(),
+            })
+        );
+        assert_eq!(
+            String::try_from(Value::Object(ClassInstance::new("Point".to_string()))),
+            Err(ValueError::TypeMismatch {
+                expected: "str",
+                got: "Point".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_value_from() {
+        assert_eq!(Value::from(42), Value::Int(42));
+        assert_eq!(Value::from(1.5), Value::Float(1.5));
+        assert_eq!(Value::from(true), Value::Bool(true));
+        assert_eq!(
+            Value::from("owned".to_string()),
+            Value::String("owned".to_string())
+        );
+
+        let borrowed = String::from("x");
+        let value: Value = borrowed.as_str().into();
+        drop(borrowed);
+        assert_eq!(value, Value::String("x".to_string()));
+
+        assert_eq!(
+            Value::from(vec![Value::Int(1)]),
+            Value::List(vec![Value::Int(1)])
+        );
+        let collected: Value = (1..=3).map(Value::from).collect();
+        assert_eq!(
+            collected,
+            Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)])
+        );
+    }
+
+    #[test]
+    fn test_field_paths() {
+        let inner =
+            ClassInstance::from_fields("Inner".to_string(), vec![("c".to_string(), Value::Int(1))]);
+        let middle = ClassInstance::from_fields(
+            "Middle".to_string(),
+            vec![
+                ("b".to_string(), Value::Object(inner)),
+                ("n".to_string(), Value::Int(5)),
+            ],
+        );
+        let mut outer = ClassInstance::from_fields(
+            "Outer".to_string(),
+            vec![("a".to_string(), Value::Object(middle))],
+        );
+
+        assert_eq!(outer.get_path("a.b.c"), Some(&Value::Int(1)));
+        assert_eq!(outer.get_path("a.n"), Some(&Value::Int(5)));
+        assert!(matches!(outer.get_path("a"), Some(Value::Object(_))));
+        assert_eq!(outer.get_path("a.n.x"), None);
+        assert_eq!(outer.get_path("a.missing.c"), None);
+
+        assert_eq!(outer.set_path("a.b.c", Value::Int(2)), Ok(()));
+        assert_eq!(outer.get_path("a.b.c"), Some(&Value::Int(2)));
+        assert_eq!(outer.set_path("a.b.d", Value::None), Ok(()));
+        assert_eq!(outer.get_path("a.b.d"), Some(&Value::None));
+
+        // A middle segment that is an int fails without modifying anything
+        let before = outer.clone();
+        assert_eq!(
+            outer.set_path("a.n.x", Value::Int(3)),
+            Err(PathError::NotAnObject {
+                path: "a.n".to_string(),
+            })
+        );
+        assert_eq!(
+            outer.set_path("a.x.y", Value::Int(3)),
+            Err(PathError::MissingField {
+                path: "a.x".to_string(),
+            })
+        );
+        assert_eq!(outer, before);
+    }
+
+    #[test]
+    fn test_fixed_size_array() {
+        let mut array = Value::array(3, Value::Int(0));
+        assert_eq!(array.len(), Some(3));
+        assert_eq!(array.array_set(1, Value::Int(7)), Ok(Value::Int(0)));
+        assert_eq!(array.array_get(1), Ok(&Value::Int(7)));
+
+        assert_eq!(
+            array.array_set(3, Value::Int(1)),
+            Err(IndexError::OutOfBounds { index: 3, len: 3 })
+        );
+        assert_eq!(
+            array.array_get(usize::MAX),
+            Err(IndexError::OutOfBounds {
+                index: usize::MAX,
+                len: 3,
+            })
+        );
+        assert_eq!(
+            Value::List(vec![]).array_get(0),
+            Err(IndexError::NotAnArray)
+        );
+
+        // Splicing may replace elements but never resize
+        assert!(array.splice(0, 1, Value::List(vec![Value::Int(5)])).is_ok());
+        assert_eq!(
+            array.splice(0, 1, Value::List(vec![])),
+            Err(IndexError::FixedSize)
+        );
+        assert_eq!(
+            array,
+            Value::Array(vec![Value::Int(5), Value::Int(7), Value::Int(0)])
+        );
+    }
+
+    #[test]
+    fn test_list_helpers() {
+        let mut list = Value::List(vec![]);
+        list.push(Value::Int(1)).unwrap();
+        list.push(Value::Int(2)).unwrap();
+        assert_eq!(list.len(), Some(2));
+        assert_eq!(list.get(1), Some(&Value::Int(2)));
+        assert_eq!(list.get(2), None);
+
+        list.set(0, Value::from("a")).unwrap();
+        assert_eq!(list.get(0), Some(&Value::from("a")));
+        assert_eq!(
+            list.set(5, Value::None),
+            Err(ValueError::Index(IndexError::OutOfBounds {
+                index: 5,
+                len: 2
+            }))
+        );
+
+        assert_eq!(list.pop(), Ok(Some(Value::Int(2))));
+        assert_eq!(list.pop(), Ok(Some(Value::from("a"))));
+        assert_eq!(list.pop(), Ok(None));
+
+        // Arrays and tuples are readable but not resizable or settable here
+        let mut array = Value::array(1, Value::None);
+        assert_eq!(array.get(0), Some(&Value::None));
+        assert_eq!(
+            array.push(Value::None),
+            Err(ValueError::TypeMismatch {
+                expected: "list",
+                got: "array".to_string(),
+            })
+        );
+        assert!(array.set(0, Value::Int(1)).is_err());
+
+        let mut number = Value::Int(3);
+        assert_eq!(
+            number.pop(),
+            Err(ValueError::TypeMismatch {
+                expected: "list",
+                got: "int".to_string(),
+            })
+        );
+        assert_eq!(number.get(0), None);
+        assert_eq!(number.len(), None);
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        let point = |x: i64| {
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![
+                    ("x".to_string(), Value::Int(x)),
+                    ("tags".to_string(), Value::List(vec![Value::from("a")])),
+                ],
+            ))
+        };
+        let original = Value::List(vec![point(1), Value::List(vec![point(2)])]);
+        let mut copy = original.deep_clone();
+        assert!(copy.structural_eq(&original));
+
+        // Mutating the copy leaves the original untouched
+        if let Value::List(items) = &mut copy {
+            if let Value::Object(instance) = &mut items[0] {
+                instance.set_field("x".to_string(), Value::Int(99));
+                instance
+                    .fields
+                    .get_mut("tags")
+                    .unwrap()
+                    .push(Value::from("b"))
+                    .unwrap();
+            }
+        }
+        assert!(!copy.structural_eq(&original));
+        assert_eq!(
+            original.deep_clone(),
+            Value::List(vec![point(1), Value::List(vec![point(2)])])
+        );
+
+        // Stricter than == for floats and dict order
+        assert_eq!(Value::Float(0.0), Value::Float(-0.0));
+        assert!(!Value::Float(0.0).structural_eq(&Value::Float(-0.0)));
+        let ab = Value::Dict(vec![
+            (Value::Int(1), Value::None),
+            (Value::Int(2), Value::None),
+        ]);
+        let ba = Value::Dict(vec![
+            (Value::Int(2), Value::None),
+            (Value::Int(1), Value::None),
+        ]);
+        assert_eq!(ab, ba);
+        assert!(!ab.structural_eq(&ba));
+
+        // Refs keep their identity
+        let reference = Value::Ref(GarbageCollector::new().allocate_ref(8).unwrap());
+        assert!(reference.deep_clone().structural_eq(&reference));
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let slot = std::mem::size_of::<Value>();
+        assert_eq!(Value::Int(1).heap_size(), slot);
+
+        let text = Value::from("x".repeat(100));
+        assert!(text.heap_size() >= slot + 100);
+
+        let a = Value::from("a".repeat(10));
+        let b = Value::Bytes(vec![0; 50]);
+        let list = Value::List(vec![a.clone(), b.clone()]);
+        // The list's slots replace the children's own `Value` slots
+        assert!(list.heap_size() >= a.heap_size() + b.heap_size() + slot);
+
+        let nested = Value::List(vec![list.clone()]);
+        assert!(nested.heap_size() > list.heap_size());
+
+        let instance = Value::Object(ClassInstance::from_fields(
+            "Point".to_string(),
+            vec![("name".to_string(), a.clone())],
+        ));
+        assert!(instance.heap_size() > a.heap_size() + "Point".len());
+    }
+
+    #[test]
+    fn test_value_iter() {
+        let list: Value = (1..=3).map(Value::from).collect();
+        let sum: i64 = list
+            .iter()
+            .unwrap()
+            .map(|v| i64::try_from(v).unwrap())
+            .sum();
+        assert_eq!(sum, 6);
+
+        let array = Value::array(2, Value::from("x"));
+        assert_eq!(array.iter().unwrap().count(), 2);
+
+        let tuple = Value::Tuple(vec![Value::Int(1), Value::None].into());
+        assert_eq!(
+            tuple.iter().unwrap().collect::<Vec<_>>(),
+            vec![&Value::Int(1), &Value::None]
+        );
+
+        let dict = Value::Dict(vec![(Value::from("k"), Value::Int(1))]);
+        assert_eq!(
+            dict.iter().unwrap().collect::<Vec<_>>(),
+            vec![&Value::from("k")]
+        );
+
+        assert!(Value::Int(1).iter().is_none());
+        assert!(Value::from("abc").iter().is_none());
+
+        let doubled: Value = list
+            .into_items()
+            .unwrap()
+            .map(|v| (&v + &v).unwrap())
+            .collect();
+        assert_eq!(doubled, (1..=3).map(|n| Value::Int(n * 2)).collect());
+        assert!(Value::None.into_items().is_none());
+    }
+
+    #[test]
+    fn test_gc_traces_instance_fields() {
+        use std::mem::size_of;
+
+        let mut gc = GarbageCollector::with_threshold(1024 * 1024);
+        let a = gc.allocate(size_of::<ClassInstance>()).unwrap().data_ptr();
+        let b = gc.allocate_ref(16).unwrap();
+        let _garbage = gc.allocate(16).unwrap();
+
+        // a holds an instance whose `next` field is a handle to b
+        let mut node = ClassInstance::new("Node".to_string());
+        node.set_field("next".to_string(), Value::Ref(b));
+        unsafe {
+            (a as *mut ClassInstance).write(node);
+            gc.set_traceable::<ClassInstance>(a);
+        }
+
+        gc.add_root(a);
+        gc.collect();
+        let (_, total, live) = gc.stats();
+        assert_eq!(total, 2);
+        assert_eq!(live, 2);
+
+        assert!(b.get(&gc).is_some());
+
+        unsafe { std::ptr::drop_in_place(a as *mut ClassInstance) };
+        gc.remove_root(a);
+        gc.collect();
+        assert_eq!(gc.stats().1, 0);
+        assert_eq!(b.get(&gc), None);
+    }
+
+    #[test]
+    fn test_value_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Value>();
+        assert_send_sync::<ClassInstance>();
+    }
+
+    #[test]
+    fn test_class_instance() {
+        let mut instance = ClassInstance::new("Point".to_string());
+        instance.set_field("x".to_string(), Value::Int(10));
+        instance.set_field("y".to_string(), Value::Int(20));
+
+        assert_eq!(instance.get_field("x"), Some(&Value::Int(10)));
+        assert_eq!(instance.get_field("y"), Some(&Value::Int(20)));
+    }
+
+    #[test]
+    fn test_class_instance_from_fields() {
+        let fields = vec![
+            ("x".to_string(), Value::Int(1)),
+            ("y".to_string(), Value::Int(2)),
+            ("name".to_string(), Value::String("p".to_string())),
+        ];
+        let mut instance = ClassInstance::from_fields("Point".to_string(), fields);
+
+        assert_eq!(instance.class_name, "Point");
+        assert_eq!(instance.fields.len(), 3);
+        assert_eq!(instance.get_field("x"), Some(&Value::Int(1)));
+        assert_eq!(instance.get_field("y"), Some(&Value::Int(2)));
+        assert_eq!(
+            instance.get_field("name"),
+            Some(&Value::String("p".to_string()))
+        );
+
+        instance.extend(vec![("z".to_string(), Value::Int(3))]);
+        assert_eq!(instance.get_field("z"), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    fn test_value_ref() {
+        let mut point = ClassInstance::new("Point".to_string());
+        point.set_field("x".to_string(), Value::Int(3));
+        let value = Value::List(vec![
+            Value::Int(1),
+            Value::String("two".to_string()),
+            Value::Object(point),
+        ]);
+
+        let view = ValueRef::new(&value);
+        assert_eq!(view.len(), Some(3));
+        assert_eq!(view.index(0).and_then(|v| v.as_int()), Some(1));
+        assert_eq!(view.index(1).and_then(|v| v.as_str()), Some("two"));
+        assert!(view.index(3).is_none());
+
+        let object = view.index(2).unwrap();
+        assert_eq!(object.class_name(), Some("Point"));
+        assert_eq!(object.get_field("x").and_then(|v| v.as_int()), Some(3));
+        assert!(object.get_field("y").is_none());
+
+        let ints: Vec<i64> = view.iter().unwrap().filter_map(|v| v.as_int()).collect();
+        assert_eq!(ints, vec![1]);
+        assert!(ValueRef::new(&Value::Int(1)).iter().is_none());
+    }
+
+    fn int_list(values: &[i64]) -> Value {
+        Value::List(values.iter().copied().map(Value::Int).collect())
+    }
+
+    #[test]
+    fn test_splice_shrink_and_grow() {
+        let mut list = int_list(&[0, 1, 2, 3, 4]);
+        let removed = list.splice(1, 4, int_list(&[9])).unwrap();
+        assert_eq!(removed, int_list(&[1, 2, 3]));
+        assert_eq!(list, int_list(&[0, 9, 4]));
+
+        let removed = list.splice(1, 2, int_list(&[5, 6, 7])).unwrap();
+        assert_eq!(removed, int_list(&[9]));
+        assert_eq!(list, int_list(&[0, 5, 6, 7, 4]));
+
+        // Empty range inserts without removing
+        let removed = list.splice(5, 5, int_list(&[8])).unwrap();
+        assert_eq!(removed, int_list(&[]));
+        assert_eq!(list, int_list(&[0, 5, 6, 7, 4, 8]));
+    }
+
+    #[test]
+    fn test_splice_errors() {
+        let mut list = int_list(&[0, 1, 2]);
+        assert_eq!(
+            list.splice(2, 4, int_list(&[])),
+            Err(IndexError::RangeOutOfBounds {
+                start: 2,
+                end: 4,
+                len: 3
+            })
+        );
+        assert!(list.splice(2, 1, int_list(&[])).is_err());
+        assert_eq!(
+            list.splice(0, 1, Value::Int(1)),
+            Err(IndexError::NotASequence)
+        );
+        assert_eq!(
+            Value::Int(1).splice(0, 0, int_list(&[])),
+            Err(IndexError::NotASequence)
+        );
+        assert_eq!(list, int_list(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn test_numeric_stats() {
+        let list = Value::List(vec![
+            Value::Int(4),
+            Value::Float(-1.5),
+            Value::Int(10),
+            Value::Float(3.5),
+        ]);
+        let stats = list.numeric_stats().unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.sum, 16.0);
+        assert_eq!(stats.min, -1.5);
+        assert_eq!(stats.max, 10.0);
+        assert_eq!(stats.mean, 4.0);
+
+        // Sums past i64::MAX are accumulated in f64 rather than overflowing
+        let big = Value::Array(vec![Value::Int(i64::MAX), Value::Int(i64::MAX)]);
+        assert_eq!(big.numeric_stats().unwrap().sum, 2.0 * i64::MAX as f64);
+    }
+
+    #[test]
+    fn test_numeric_stats_errors() {
+        assert!(Value::List(vec![]).numeric_stats().is_err());
+        assert!(
+            Value::List(vec![Value::Int(1), Value::String("2".to_string())])
+                .numeric_stats()
+                .is_err()
+        );
+        assert!(Value::Int(1).numeric_stats().is_err());
+    }
+
+    #[test]
+    fn test_compare_chain() {
+        use CompareOp::*;
+
+        // 1 < 2.5 <= 3 == 3.0
+        let operands = vec![
+            Value::Int(1),
+            Value::Float(2.5),
+            Value::Int(3),
+            Value::Float(3.0),
+        ];
+        assert_eq!(
+            Value::compare_chain(&operands, &[Lt, Le, Eq]),
+            Ok(Value::Bool(true))
+        );
+
+        // 3 < 2 is false, so the string is never compared against 2
+        let operands = vec![Value::Int(3), Value::Int(2), Value::String("x".to_string())];
+        assert_eq!(
+            Value::compare_chain(&operands, &[Lt, Lt]),
+            Ok(Value::Bool(false))
+        );
+
+        // With a true first link, the type error in the middle surfaces
+        let operands = vec![Value::Int(1), Value::Int(2), Value::String("x".to_string())];
+        assert!(Value::compare_chain(&operands, &[Lt, Lt]).is_err());
+
+        assert!(Value::compare_chain(&operands, &[Lt]).is_err());
+        assert_eq!(
+            Value::Float(f64::NAN).compare(Ne, &Value::Float(f64::NAN)),
+            Ok(true)
+        );
+        assert_eq!(Value::Int(1).compare(Eq, &Value::None), Ok(false));
+    }
+
+    #[test]
+    fn test_filled_nested() {
+        let fill = Value::Float(0.5);
+        assert_eq!(Value::filled_nested(&[], fill.clone()), fill);
+
+        let matrix = Value::filled_nested(&[2, 3], fill.clone());
+        let rows = match &matrix {
+            Value::List(rows) => rows,
+            _ => panic!("expected list"),
+        };
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            match row {
+                Value::List(cells) => {
+                    assert_eq!(cells.len(), 3);
+                    assert!(cells.iter().all(|cell| *cell == fill));
+                }
+                _ => panic!("expected nested list"),
+            }
+        }
+
+        assert_eq!(
+            Value::filled_nested(&[2, 0], Value::None),
+            Value::List(vec![Value::List(vec![]), Value::List(vec![])])
+        );
+    }
+
+    #[test]
+    fn test_deep_eq_report() {
+        let point = |x: f64, y: f64| {
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![
+                    ("x".to_string(), Value::Float(x)),
+                    ("y".to_string(), Value::Float(y)),
+                ],
+            ))
+        };
+
+        let left = Value::List(vec![point(1.0, 2.0), point(3.0, f64::NAN), Value::Int(7)]);
+        assert!(left.deep_eq_report(&left.clone()).is_err()); // NaN != NaN
+
+        let same = Value::List(vec![point(1.0, 2.0), Value::Int(7)]);
+        assert_eq!(same.deep_eq_report(&same.clone()), Ok(()));
+
+        let left = Value::List(vec![point(1.0, 2.0), point(3.0, 4.0), Value::Int(7)]);
+        let right = Value::List(vec![point(1.0, 2.5), point(3.5, 4.0), Value::Int(8)]);
+        let diffs = left.deep_eq_report(&right).unwrap_err();
+        let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["$[0].y", "$[1].x", "$[2]"]);
+        assert_eq!(diffs[0].left, Some(Value::Float(2.0)));
+        assert_eq!(diffs[0].right, Some(Value::Float(2.5)));
+        assert_eq!(diffs[2].right, Some(Value::Int(8)));
+
+        // Missing elements are reported with an absent side
+        let shorter = Value::List(vec![point(1.0, 2.0), point(3.0, 4.0)]);
+        let diffs = left.deep_eq_report(&shorter).unwrap_err();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$[2]");
+        assert_eq!(diffs[0].right, None);
+    }
+
+    #[test]
+    fn test_deep_eq_report_tuples_and_dicts() {
+        let nan_tuple = Value::Tuple(vec![Value::Int(1), Value::Float(f64::NAN)].into());
+        let diffs = nan_tuple.deep_eq_report(&nan_tuple.clone()).unwrap_err();
+        assert_eq!(diffs[0].path, "$[1]");
+
+        let dict = |x: f64| {
+            Value::Dict(vec![
+                (Value::from("a"), Value::Int(1)),
+                (Value::Int(2), Value::List(vec![Value::Float(x)])),
+            ])
+        };
+        let diffs = dict(f64::NAN).deep_eq_report(&dict(f64::NAN)).unwrap_err();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$[2][0]");
+        assert_eq!(dict(1.0).deep_eq_report(&dict(1.0)), Ok(()));
+
+        // Near-equal floats inside tuples and dicts pass with an epsilon
+        let near = Value::Tuple(vec![dict(0.1 + 0.2)].into());
+        let exact = Value::Tuple(vec![dict(0.3)].into());
+        assert!(near.deep_eq_report(&exact).is_err());
+        assert_eq!(near.approx_eq_report(&exact, 1e-9), Ok(()));
+        crate::assert_value_approx_eq!(near, exact, 1e-9);
+
+        // Dict differences are reported per key, missing keys with an absent side
+        let left = Value::Dict(vec![
+            (Value::from("a"), Value::Int(1)),
+            (Value::from("b"), Value::Int(2)),
+        ]);
+        let right = Value::Dict(vec![
+            (Value::from("c"), Value::Int(3)),
+            (Value::from("a"), Value::Int(9)),
+        ]);
+        let diffs = left.deep_eq_report(&right).unwrap_err();
+        let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec![r#"$["a"]"#, r#"$["b"]"#, r#"$["c"]"#]);
+        assert_eq!(diffs[1].right, None);
+        assert_eq!(diffs[2].left, None);
+    }
+
+    #[test]
+    fn test_assert_value_macros() {
+        let nested = Value::List(vec![
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![("x".to_string(), Value::Float(0.1 + 0.2))],
+            )),
+            Value::List(vec![Value::Int(1), Value::String("a".to_string())]),
+        ]);
+        crate::assert_value_eq!(nested, nested.clone());
+
+        let rounded = Value::List(vec![
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![("x".to_string(), Value::Float(0.3))],
+            )),
+            Value::List(vec![Value::Int(1), Value::String("a".to_string())]),
+        ]);
+        assert!(nested.deep_eq_report(&rounded).is_err());
+        crate::assert_value_approx_eq!(nested, rounded, 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "$[1].x: Int(2) != Int(3)")]
+    fn test_assert_value_eq_reports_diff_path() {
+        let point = |x| {
+            Value::Object(ClassInstance::from_fields(
+                "Point".to_string(),
+                vec![("x".to_string(), Value::Int(x))],
+            ))
+        };
+        crate::assert_value_eq!(
+            Value::List(vec![point(1), point(2)]),
+            Value::List(vec![point(1), point(3)])
+        );
+    }
+
+    fn config(fields: Vec<(&str, Value)>) -> Value {
+        let mut instance = ClassInstance::new("Config".to_string());
+        for (name, value) in fields {
+            instance.set_field(name.to_string(), value);
+        }
+        Value::Object(instance)
+    }
+
+    #[test]
+    fn test_deep_merge_nested() {
+        let base = config(vec![
+            ("name", Value::String("app".to_string())),
+            (
+                "db",
+                config(vec![
+                    ("host", Value::String("localhost".to_string())),
+                    ("port", Value::Int(5432)),
+                ]),
+            ),
+        ]);
+        let overlay = config(vec![
+            ("db", config(vec![("port", Value::Int(6543))])),
+            ("debug", Value::Bool(true)),
+        ]);
+
+        let merged = base.deep_merge(&overlay).unwrap();
+        let expected = config(vec![
+            ("name", Value::String("app".to_string())),
+            (
+                "db",
+                config(vec![
+                    ("host", Value::String("localhost".to_string())),
+                    ("port", Value::Int(6543)),
+                ]),
+            ),
+            ("debug", Value::Bool(true)),
+        ]);
+        assert_eq!(merged, expected);
+
+        // Type mismatch at a key lets the overlay win
+        let overlay = config(vec![("db", Value::None)]);
+        let merged = base.deep_merge(&overlay).unwrap();
+        match merged {
+            Value::Object(instance) => assert_eq!(instance.get_field("db"), Some(&Value::None)),
+            _ => panic!("expected object"),
+        }
+
+        assert!(Value::Int(1).deep_merge(&overlay).is_err());
+    }
+
+    #[test]
+    fn test_deep_merge_lists() {
+        let base = config(vec![(
+            "tags",
+            Value::List(vec![Value::Int(1), Value::Int(2)]),
+        )]);
+        let overlay = config(vec![("tags", Value::List(vec![Value::Int(3)]))]);
+
+        let replaced = base.deep_merge(&overlay).unwrap();
+        assert_eq!(
+            replaced,
+            config(vec![("tags", Value::List(vec![Value::Int(3)]))])
+        );
+
+        let concatenated = base.deep_merge_with(&overlay, ListMerge::Concat).unwrap();
+        assert_eq!(
+            concatenated,
+            config(vec![(
+                "tags",
+                Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)])
+            )])
+        );
+    }
+
+    #[test]
+    fn test_object() {
+        let obj = Object::new(Value::Int(42));
+        assert_eq!(obj.as_int(), Some(42));
+        assert_eq!(obj.as_float(), Some(42.0));
+    }
+
+    #[test]
+    fn test_native_functions() {
+        let mut rt = Runtime::new().unwrap();
+        let add = rt.register_native(
+            "add",
+            2,
+            Box::new(|_, args| match (&args[0], &args[1]) {
+                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a + b)),
+                _ => Err(TypeError("add expects ints".to_string()).into()),
+            }),
+        );
+
+        assert_eq!(rt.lookup_native("add"), Some(add));
+        assert_eq!(
+            rt.call_native(&add, &[Value::Int(2), Value::Int(40)]),
+            Ok(Value::Int(42))
+        );
+        assert!(matches!(
+            rt.call_native(&add, &[Value::Int(1), Value::None]),
+            Err(RuntimeError::Type(_))
+        ));
+        assert_eq!(
+            rt.call_native(&add, &[Value::Int(1)]),
+            Err(RuntimeError::ArityMismatch {
+                name: "add".to_string(),
+                expected: 2,
+                got: 1,
+            })
+        );
+
+        // Natives receive the runtime and may call other natives
+        let twice = rt.register_native(
+            "twice",
+            1,
+            Box::new(|rt, args| {
+                let add = rt
+                    .lookup_native("add")
+                    .ok_or(RuntimeError::UnknownFunction)?;
+                rt.call_native(&add, &[args[0].clone(), args[0].clone()])
+            }),
+        );
+        assert_eq!(
+            rt.call_native(&twice, &[Value::Int(21)]),
+            Ok(Value::Int(42))
+        );
+    }
+
+    #[test]
+    fn test_class_methods() {
+        let mut rt = Runtime::new().unwrap();
+        let area = rt.register_native(
+            "Rect.area",
+            1,
+            Box::new(|_, args| {
+                let rect = match &args[0] {
+                    Value::Object(rect) => rect,
+                    _ => return Err(TypeError("area expects a Rect".to_string()).into()),
+                };
+                let side = |name| i64::try_from(rect.get_field(name).unwrap_or(&Value::None));
+                Ok(Value::Int(side("w")? * side("h")?))
+            }),
+        );
+        let name = rt.register_native("Rect.name", 1, Box::new(|_, _| Ok("rect".into())));
+        rt.register_class(
+            ClassDef::new("Rect")
+                .with_method("area", area)
+                .with_method("name", name),
+        );
+
+        assert_eq!(rt.lookup_method("Rect", "area"), Some(area));
+        assert_eq!(rt.lookup_method("Rect", "name"), Some(name));
+        assert_eq!(rt.lookup_method("Rect", "perimeter"), None);
+        assert_eq!(rt.lookup_method("Circle", "area"), None);
+        assert_eq!(rt.class("Rect").map(ClassDef::name), Some("Rect"));
+
+        let mut rect = rt.new_instance("Rect").unwrap();
+        rect.set_field("w".to_string(), Value::Int(3));
+        rect.set_field("h".to_string(), Value::Int(4));
+        let method = rt.lookup_method(&rect.class_name, "area").unwrap();
+        assert_eq!(
+            rt.call_native(&method, &[Value::Object(rect)]),
+            Ok(Value::Int(12))
+        );
+
+        assert_eq!(
+            rt.new_instance("Circle"),
+            Err(RuntimeError::UnknownClass("Circle".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_class_inheritance() {
+        let mut rt = Runtime::new().unwrap();
+        let describe = rt.register_native("describe", 1, Box::new(|_, _| Ok("shape".into())));
+        let area = rt.register_native("area", 1, Box::new(|_, _| Ok(Value::Int(0))));
+        let square_area = rt.register_native("Square.area", 1, Box::new(|_, _| Ok(Value::Int(4))));
+        rt.register_class(
+            ClassDef::new("Shape")
+                .with_method("describe", describe)
+                .with_field("sides", Value::Int(0))
+                .with_field("color", "black".into()),
+        );
+        rt.register_class(
+            ClassDef::new("Rect")
+                .with_parent("Shape")
+                .with_method("area", area)
+                .with_field("sides", Value::Int(4)),
+        );
+        rt.register_class(
+            ClassDef::new("Square")
+                .with_parent("Rect")
+                .with_method("area", square_area),
+        );
+
+        // Grandparent method resolves from the grandchild, overrides win
+        assert_eq!(rt.resolve_method("Square", "describe"), Ok(Some(describe)));
+        assert_eq!(rt.resolve_method("Square", "area"), Ok(Some(square_area)));
+        assert_eq!(rt.resolve_method("Rect", "area"), Ok(Some(area)));
+        assert_eq!(rt.resolve_method("Square", "volume"), Ok(None));
+        assert_eq!(rt.lookup_method("Square", "describe"), None);
+
+        assert_eq!(rt.is_subclass_of("Square", "Shape"), Ok(true));
+        assert_eq!(rt.is_subclass_of("Square", "Square"), Ok(true));
+        assert_eq!(rt.is_subclass_of("Shape", "Square"), Ok(false));
+
+        let square = rt.new_instance("Square").unwrap();
+        assert_eq!(square.get_field("sides"), Some(&Value::Int(4)));
+        assert_eq!(
+            square.get_field("color"),
+            Some(&Value::String("black".to_string()))
+        );
+
+        // Cycles and dangling parents are reported instead of looping
+        rt.register_class(ClassDef::new("A").with_parent("B"));
+        rt.register_class(ClassDef::new("B").with_parent("A"));
+        assert_eq!(
+            rt.resolve_method("A", "describe"),
+            Err(RuntimeError::InheritanceCycle("A".to_string()))
+        );
+        assert!(rt.is_subclass_of("B", "Shape").is_err());
+        rt.register_class(ClassDef::new("Orphan").with_parent("Missing"));
+        assert_eq!(
+            rt.new_instance("Orphan"),
+            Err(RuntimeError::UnknownClass("Missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_alloc_value() {
+        let mut rt = Runtime::new().unwrap();
+        let ptr = rt.alloc_value(Value::from("stored in the arena")).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % std::mem::align_of::<Value>(), 0);
+
+        let value = unsafe { ptr.as_ref() };
+        assert_eq!(value, &Value::from("stored in the arena"));
+        unsafe { rt.dealloc_value(ptr) };
+
+        // The released slot is recycled for the next value
+        let again = rt.alloc_value(Value::List(vec![Value::Int(1)])).unwrap();
+        assert_eq!(again, ptr);
+        assert_eq!(unsafe { again.as_ref() }.get(0), Some(&Value::Int(1)));
+        unsafe { rt.dealloc_value(again) };
+    }
+
+    #[test]
+    fn test_string_interning() {
+        let mut rt = Runtime::new().unwrap();
+        let name = rt.intern("name");
+        let value = rt.intern("value");
+        let empty = rt.intern("");
+        assert_eq!(rt.intern(&String::from("name")), name);
+        assert_ne!(name, value);
+        assert_ne!(empty, name);
+
+        // Interned strings survive an arena reset
+        rt.reset();
+        assert_eq!(rt.resolve(name), Some("name"));
+        assert_eq!(rt.resolve(value), Some("value"));
+        assert_eq!(rt.resolve(empty), Some(""));
+
+        // Filling several chunks keeps earlier strings intact
+        let long = "x".repeat(INTERNER_CHUNK_SIZE);
+        let ids: Vec<InternId> = (0..4)
+            .map(|i| rt.intern(&format!("{}{}", long, i)))
+            .collect();
+        assert_eq!(rt.resolve(ids[0]), Some(format!("{}0", long).as_str()));
+        assert_eq!(rt.resolve(name), Some("name"));
+
+        let other = Runtime::new().unwrap();
+        assert_eq!(other.resolve(ids[3]), None);
+    }
+
+    #[test]
+    fn test_runtime_gc_strategy() {
+        let mut rt = Runtime::with_strategy(GcStrategy::None, 1024).unwrap();
+        rt.allocate(64, 8).unwrap();
+        rt.gc_collect();
+        assert_eq!(rt.gc_stats(), (0, 0, 0));
+        assert_eq!(rt.collect_garbage(), 0);
+
+        for strategy in [GcStrategy::MarkSweep, GcStrategy::Generational] {
+            let mut rt = Runtime::with_strategy(strategy, 1024).unwrap();
+            assert_eq!(rt.collect_garbage(), 0);
+            assert_eq!(rt.gc.as_ref().unwrap().collection_count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_runtime_metrics() {
+        let mut rt = Runtime::new().unwrap();
+        assert_eq!(rt.metrics().used, 0);
+        rt.allocate(100, 8).unwrap();
+        rt.allocate(2 * 1024 * 1024, 8).unwrap();
+        rt.collect_garbage();
+
+        let metrics = rt.metrics();
+        let (used, capacity) = rt.memory_stats();
+        assert_eq!((metrics.used, metrics.capacity), (used, capacity));
+        assert_eq!(metrics.allocator_count, 2);
+        assert_eq!((metrics.total_allocated, metrics.object_count), (0, 0));
+        assert_eq!(metrics.live_count, 0);
+        assert_eq!(metrics.collection_count, 1);
+
+        let rt = Runtime::with_strategy(GcStrategy::None, 1024).unwrap();
+        assert_eq!(rt.metrics().collection_count, 0);
+        assert_eq!(rt.metrics().last_pause, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_runtime_hooks() {
+        let sizes = Rc::new(RefCell::new(Vec::new()));
+        let collections = Rc::new(RefCell::new(Vec::new()));
+        let mut rt = Runtime::new().unwrap();
+        let log = Rc::clone(&sizes);
+        rt.on_allocate(Box::new(move |size| log.borrow_mut().push(size)));
+        let log = Rc::clone(&collections);
+        rt.on_collect(Box::new(move |metrics| {
+            log.borrow_mut().push(metrics.collection_count)
+        }));
+
+        rt.allocate(24, 8).unwrap();
+        rt.allocate_layout(Layout::new::<u64>()).unwrap();
+        rt.alloc_value(Value::Int(1)).unwrap();
+        assert_eq!(*sizes.borrow(), [24, 8, mem::size_of::<Value>()]);
+
+        rt.collect_garbage();
+        rt.gc_collect();
+        assert_eq!(*collections.borrow(), [1, 2]);
+
+        // Nothing is collected, so nothing is reported, without a collector
+        let mut rt = Runtime::with_strategy(GcStrategy::None, 1024).unwrap();
+        let log = Rc::clone(&collections);
+        rt.on_collect(Box::new(move |metrics| {
+            log.borrow_mut().push(metrics.collection_count)
+        }));
+        rt.collect_garbage();
+        assert_eq!(collections.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_runtime_reset_clears_roots() {
+        let mut rt = Runtime::new().unwrap();
+        let mut outside = 0u64;
+        let outside = ptr::addr_of_mut!(outside).cast::<u8>();
+        let bumped = rt.allocate(4096, 8).unwrap().as_ptr();
+        let pooled = rt.allocate(16, 8).unwrap().as_ptr();
+        rt.add_root(bumped);
+        rt.add_root(pooled);
+        rt.add_root(outside);
+        assert_eq!(rt.root_count(), 3);
+
+        // Only the root outside the arena survives
+        rt.reset();
+        assert_eq!(rt.root_count(), 1);
+        rt.gc_collect();
+
+        let ptr = rt.allocate(8, 8).unwrap().as_ptr();
+        rt.add_root(ptr);
+        rt.reset_and_clear_gc();
+        assert_eq!(rt.root_count(), 0);
+        assert_eq!(rt.gc_stats().1, 0);
+    }
+
+    #[test]
+    fn test_runtime_handles() {
+        let mut rt = Runtime::new().unwrap();
+        let list = rt.new_object(Value::List(vec![Value::Int(1)]));
+        let name = rt.new_object(Value::from("pain"));
+        assert_eq!(rt.get(name), Some(&Value::from("pain")));
+
+        rt.get_mut(list).unwrap().push(Value::Int(2)).unwrap();
+        assert_eq!(
+            rt.get(list),
+            Some(&Value::List(vec![Value::Int(1), Value::Int(2)]))
+        );
+
+        assert_eq!(rt.free_object(name), Some(Value::from("pain")));
+        assert_eq!(rt.free_object(name), None);
+        assert_eq!(rt.get(name), None);
+
+        // The freed slot is reused, but the old handle stays stale
+        let reused = rt.new_object(Value::Int(3));
+        assert_ne!(reused, name);
+        assert_eq!(rt.get(name), None);
+        assert!(rt.get_mut(name).is_none());
+        assert_eq!(rt.get(reused), Some(&Value::Int(3)));
+        assert_eq!(rt.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_runtime_thread_local() {
+        assert!(Runtime::install(Runtime::new().unwrap()).is_none());
+        let handle = Runtime::with_current(|rt| {
+            rt.allocate(64, 8).unwrap();
+            rt.new_object(Value::Int(7))
+        });
+        let used = Runtime::with_current(|rt| rt.memory_stats().0);
+        assert_eq!(used, 64);
+
+        // Nested access panics instead of aliasing the runtime
+        let nested =
+            std::panic::catch_unwind(|| Runtime::with_current(|_| Runtime::with_current(|_| ())));
+        assert!(nested.is_err());
+
+        let rt = Runtime::uninstall().unwrap();
+        assert_eq!(rt.get(handle), Some(&Value::Int(7)));
+        assert!(Runtime::uninstall().is_none());
+        assert!(std::panic::catch_unwind(|| Runtime::with_current(|_| ())).is_err());
+
+        // Runtimes on other threads are independent
+        Runtime::install(Runtime::new().unwrap());
+        std::thread::spawn(|| assert!(Runtime::uninstall().is_none()))
+            .join()
+            .unwrap();
+        assert!(Runtime::uninstall().is_some());
+    }
+
+    #[test]
+    fn test_runtime_max_value_depth() {
+        let mut rt = Runtime::new().unwrap();
+        assert_eq!(rt.max_value_depth(), DEFAULT_MAX_VALUE_DEPTH);
+
+        let mut deep = Value::Int(0);
+        for _ in 0..10_000 {
+            deep = Value::List(vec![deep]);
+        }
+        let exceeded = Some(ValueError::DepthExceeded(DEFAULT_MAX_VALUE_DEPTH));
+        assert_eq!(rt.deep_clone(&deep).err(), exceeded);
+        assert_eq!(rt.deep_eq(&deep, &deep).err(), exceeded);
+        assert_eq!(rt.deep_size(&deep).err(), exceeded);
+
+        let shallow = Value::Dict(vec![(Value::from("k"), Value::List(vec![Value::Int(1)]))]);
+        assert_eq!(rt.deep_clone(&shallow), Ok(shallow.clone()));
+        assert_eq!(rt.deep_eq(&shallow, &shallow), Ok(true));
+        assert_eq!(rt.deep_size(&shallow), Ok(shallow.heap_size()));
+
+        rt.set_max_value_depth(1);
+        assert_eq!(rt.deep_clone(&shallow), Err(ValueError::DepthExceeded(1)));
+
+        // Dismantle iteratively, since dropping recurses as deep as the nesting
+        while let Value::List(mut items) = deep {
+            deep = items.pop().unwrap_or(Value::None);
+        }
+    }
+
+    #[test]
+    fn test_runtime_snapshot_restore() {
+        let mut rt = Runtime::new().unwrap();
+        let kept = rt.allocate(4096, 8).unwrap();
+        unsafe { kept.as_ptr().write_bytes(7, 4096) };
+        let snapshot = rt.snapshot();
+        let (used, _) = rt.memory_stats();
+
+        unsafe { kept.as_ptr().write_bytes(0, 4096) };
+        rt.allocate(64, 8).unwrap();
+        rt.allocate(8192, 8).unwrap();
+        assert!(rt.memory_stats().0 > used);
+
+        rt.restore(&snapshot).unwrap();
+        assert_eq!(rt.memory_stats().0, used);
+        assert_eq!(unsafe { *kept.as_ptr().add(4095) }, 7);
     }
 
     #[test]
@@ -196,6 +3696,8 @@ mod tests {
         let mut rt = Runtime::new().unwrap();
         let ptr = rt.allocate(64, 8);
         assert!(ptr.is_some());
+        let ptr = rt.allocate_layout(Layout::new::<[u64; 4]>()).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % 8, 0);
 
         let (used, capacity) = rt.memory_stats();
         assert!(used > 0);
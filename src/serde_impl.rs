@@ -0,0 +1,290 @@
+// serde support for runtime values
+// Scalars map to the format's native scalars and lists to sequences. Arrays
+// and tuples are wrapped as {"$array": [...]} and {"$tuple": [...]} so they
+// survive a round trip, dicts as {"$dict": [[key, value], ...]} since keys
+// may be any value, byte buffers as {"$bytes": [...]}, and objects as
+// {"class_name": ..., "fields": {...}}
+
+use crate::object::{ClassInstance, Value};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeStruct, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+
+const ARRAY_KEY: &str = "$array";
+const DICT_KEY: &str = "$dict";
+const TUPLE_KEY: &str = "$tuple";
+const BYTES_KEY: &str = "$bytes";
+const INSTANCE_FIELDS: &[&str] = &["class_name", "fields"];
+
+/// Non-finite floats follow the format's own rules, so JSON turns them into
+/// null and they come back as `None`. GC references cannot be serialized
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Int(n) => serializer.serialize_i64(*n),
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::None => serializer.serialize_unit(),
+            Value::Object(instance) => instance.serialize(serializer),
+            Value::List(items) => items.serialize(serializer),
+            Value::Array(items) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(ARRAY_KEY, items)?;
+                map.end()
+            }
+            Value::Bytes(bytes) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(BYTES_KEY, bytes)?;
+                map.end()
+            }
+            Value::Tuple(items) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(TUPLE_KEY, items)?;
+                map.end()
+            }
+            Value::Dict(entries) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(DICT_KEY, entries)?;
+                map.end()
+            }
+            Value::Ref(_) => Err(ser::Error::custom("GC references cannot be serialized")),
+        }
+    }
+}
+
+/// Fields are written in name order so output is deterministic
+impl Serialize for ClassInstance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ClassInstance", 2)?;
+        state.serialize_field("class_name", &self.class_name)?;
+        state.serialize_field("fields", &SortedFields(self))?;
+        state.end()
+    }
+}
+
+struct SortedFields<'a>(&'a ClassInstance);
+
+impl Serialize for SortedFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = self.0.sorted_fields();
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (name, value) in fields {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a runtime value")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::Int(n))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+        i64::try_from(n)
+            .map(Value::Int)
+            .map_err(|_| E::custom("integer does not fit in i64"))
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Value, E> {
+        Ok(Value::Float(f))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let first = map.next_key::<String>()?;
+        let value = match first.as_deref() {
+            Some(ARRAY_KEY) => Value::Array(map.next_value()?),
+            Some(DICT_KEY) => Value::Dict(map.next_value()?),
+            Some(TUPLE_KEY) => Value::Tuple(map.next_value()?),
+            Some(BYTES_KEY) => Value::Bytes(map.next_value()?),
+            _ => return instance_from_map(first, map).map(Value::Object),
+        };
+        if map.next_key::<String>()?.is_some() {
+            return Err(de::Error::custom("unexpected key in tagged value"));
+        }
+        Ok(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClassInstance {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ClassInstance, D::Error> {
+        deserializer.deserialize_struct("ClassInstance", INSTANCE_FIELDS, InstanceVisitor)
+    }
+}
+
+struct InstanceVisitor;
+
+impl<'de> Visitor<'de> for InstanceVisitor {
+    type Value = ClassInstance;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a class instance")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ClassInstance, A::Error> {
+        let class_name = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let fields: HashMap<String, Value> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(ClassInstance::from_fields(class_name, fields))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ClassInstance, A::Error> {
+        let first = map.next_key()?;
+        instance_from_map(first, map)
+    }
+}
+
+/// Read the `class_name` and `fields` entries of an instance map, in any
+/// order, given the key that has already been consumed
+fn instance_from_map<'de, A: MapAccess<'de>>(
+    mut key: Option<String>,
+    mut map: A,
+) -> Result<ClassInstance, A::Error> {
+    let mut class_name: Option<String> = None;
+    let mut fields: Option<HashMap<String, Value>> = None;
+    while let Some(name) = key {
+        match name.as_str() {
+            "class_name" if class_name.is_none() => class_name = Some(map.next_value()?),
+            "fields" if fields.is_none() => fields = Some(map.next_value()?),
+            "class_name" => return Err(de::Error::duplicate_field("class_name")),
+            "fields" => return Err(de::Error::duplicate_field("fields")),
+            _ => return Err(de::Error::unknown_field(&name, INSTANCE_FIELDS)),
+        }
+        key = map.next_key()?;
+    }
+
+    let class_name = class_name.ok_or_else(|| de::Error::missing_field("class_name"))?;
+    Ok(ClassInstance::from_fields(
+        class_name,
+        fields.unwrap_or_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: &Value) -> Value {
+        let json = serde_json::to_string(value).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_serde_json_round_trip() {
+        let point = ClassInstance::from_fields(
+            "Point".to_string(),
+            vec![
+                ("x".to_string(), Value::Int(1)),
+                ("y".to_string(), Value::Float(2.0)),
+            ],
+        );
+        let values = [
+            Value::Int(-7),
+            Value::Float(1.0),
+            Value::Bool(true),
+            Value::String("hi".to_string()),
+            Value::Bytes(vec![0, 7, 255]),
+            Value::None,
+            Value::List(vec![Value::Int(1), Value::None]),
+            Value::Array(vec![Value::Int(1), Value::Int(2)]),
+            Value::Tuple(vec![Value::Int(1), Value::String("a".to_string())].into()),
+            Value::Dict(vec![
+                (Value::Int(1), Value::String("one".to_string())),
+                (Value::None, Value::List(vec![])),
+            ]),
+            Value::Object(point.clone()),
+            Value::List(vec![
+                Value::Array(vec![Value::Object(point.clone())]),
+                Value::Object(ClassInstance::from_fields(
+                    "Line".to_string(),
+                    vec![
+                        ("from".to_string(), Value::Object(point.clone())),
+                        ("tags".to_string(), Value::List(vec![])),
+                    ],
+                )),
+            ]),
+        ];
+        for value in &values {
+            assert_eq!(&round_trip(value), value);
+        }
+
+        // Arrays stay distinct from lists
+        assert!(matches!(round_trip(&Value::Array(vec![])), Value::Array(_)));
+
+        assert_eq!(
+            serde_json::to_string(&Value::Object(point.clone())).unwrap(),
+            r#"{"class_name":"Point","fields":{"x":1,"y":2.0}}"#
+        );
+        let instance: ClassInstance =
+            serde_json::from_str(r#"{"fields":{"x":1,"y":2.0},"class_name":"Point"}"#).unwrap();
+        assert_eq!(instance, point);
+    }
+
+    #[test]
+    fn test_serde_errors() {
+        let handle = crate::GarbageCollector::new().allocate_ref(8).unwrap();
+        assert!(serde_json::to_string(&Value::Ref(handle)).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"fields":{}}"#).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"$array":[],"x":1}"#).is_err());
+        assert!(serde_json::from_str::<Value>("18446744073709551615").is_err());
+    }
+    #[test]
+    fn test_serde_runtime_metrics() {
+        let metrics = crate::Runtime::new().unwrap().metrics();
+        let json = serde_json::to_value(metrics).unwrap();
+        assert_eq!(json["capacity"], metrics.capacity);
+        assert_eq!(json["collection_count"], 0);
+        assert_eq!(json["last_pause"]["nanos"], 0);
+    }
+}